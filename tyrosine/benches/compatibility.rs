@@ -0,0 +1,109 @@
+//! Compares the merge-based compatibility distance against the old HashMap version
+//! Run with `cargo bench --bench compatibility`
+
+use std::{collections::{BTreeSet, HashMap}, hint::black_box, time::{Duration, Instant}};
+use tyrosine::{config::{Config, InitialTopology, WeightInit}, genome::{Genome, GlobalInnovator}, phenotype::Phenotype, species::{Species, SpeciesCounter}};
+
+const MEMBERS: usize = 10_000;
+const SPECIES: usize = 100;
+
+
+
+/// Gene counts the way they were tallied before, building two maps and a set for every pair
+/// (excess, disjoint, matching, summed weight difference, number of distinct innovation numbers)
+fn hashmap_terms(g1: &Genome, g2: &Genome) -> (usize, usize, usize, f64, usize) {
+    let g1_map: HashMap<usize, _> = g1.connection_genes.iter().map(|c| (c.innov, c)).collect();
+    let g2_map: HashMap<usize, _> = g2.connection_genes.iter().map(|c| (c.innov, c)).collect();
+    let all_innovs: BTreeSet<usize> = g1_map.keys().chain(g2_map.keys()).cloned().collect();
+    let (mut matching, mut weight_diff, mut disjoint, mut excess) = (0, 0.0, 0, 0);
+    let max1 = g1_map.keys().max().cloned().unwrap_or(0);
+    let max2 = g2_map.keys().max().cloned().unwrap_or(0);
+    for innov in &all_innovs {
+        match (g1_map.get(innov), g2_map.get(innov)) {
+            (Some(a), Some(b)) => {
+                matching += 1;
+                weight_diff += (a.weight - b.weight).abs();
+            },
+            (Some(_), None) if *innov <= max2 => disjoint += 1,
+            (None, Some(_)) if *innov <= max1 => disjoint += 1,
+            _ => excess += 1,
+        }
+    }
+    (excess, disjoint, matching, weight_diff, all_innovs.len())
+}
+
+
+/// The distance as it was computed before, building two maps and a set for every pair
+fn hashmap_distance(g1: &Genome, g2: &Genome) -> f64 {
+    let (excess, disjoint, matching, weight_diff, innovs) = hashmap_terms(g1, g2);
+    let n = innovs.max(1) as f64;
+    let w = if matching > 0 { weight_diff / matching as f64 } else { 100.0 };
+    (excess as f64) / n + (disjoint as f64) / n + 0.4 * w
+}
+
+
+/// Varied networks sharing one innovation history, like a population many generations in
+fn phenotypes(count: usize, config: &Config, innovator: &mut GlobalInnovator) -> Vec<Phenotype> {
+    let mut innovations = HashMap::new();
+    (0..count)
+        .map(|_| {
+            let genome = Genome::with_topology(8, 4, config.initial_topology, config, innovator, &mut innovations);
+            let mut phenotype = Phenotype::from_genome(genome).unwrap(); //initial topologies are feed-forward
+            for _ in 0..20 {
+                phenotype = Phenotype::from_mutation(&phenotype.genome, config, innovator, &mut innovations);
+            }
+            phenotype
+        })
+        .collect()
+}
+
+
+fn time<F: FnOnce() -> f64>(name: &str, f: F) -> Duration {
+    let start = Instant::now();
+    black_box(f());
+    let elapsed = start.elapsed();
+    println!("{:<28} {:>10.1?}", name, elapsed);
+    elapsed
+}
+
+
+fn main() {
+    let mut config = Config {
+        initial_topology: InitialTopology::FullyConnectedHidden(4),
+        weight_init: WeightInit::Uniform { min: -1.0, max: 1.0 },
+        ..Config::default()
+    };
+    config.mutation.add_connection = 0.5;
+    config.mutation.add_node = 0.2;
+    let mut innovator = GlobalInnovator::new();
+    let members = phenotypes(MEMBERS, &config, &mut innovator);
+    let representatives = phenotypes(SPECIES, &config, &mut innovator).into_iter().map(|p| p.genome).collect::<Vec<_>>();
+    let genes = members.iter().map(|p| p.genome.connection_genes.len()).sum::<usize>() as f64 / MEMBERS as f64;
+    println!("{} members against {} representatives, {:.1} connections per genome", MEMBERS, SPECIES, genes);
+
+    // the old tallies under the larger-genome normalizer agree with the merge, without node biases or aggregation mutations
+    for (m, r) in members.iter().take(SPECIES).zip(&representatives) {
+        let (excess, disjoint, matching, weight_diff, _) = hashmap_terms(&m.genome, r);
+        let n = m.genome.connection_genes.len().max(r.connection_genes.len()).max(1) as f64;
+        let w = if matching > 0 { weight_diff / matching as f64 } else { 0.0 };
+        let old = (excess as f64) / n + (disjoint as f64) / n + 0.4 * w;
+        assert!((old - Species::compatibility_distance(&m.genome, r)).abs() < 1e-9, "Merge distance matches the hashmap tallies.");
+    }
+
+    // every member against every representative, the worst case for first-fit speciation
+    let old = time("hashmap distance", || {
+        members.iter().flat_map(|m| representatives.iter().map(move |r| hashmap_distance(&m.genome, r))).sum()
+    });
+    let new = time("merge distance", || {
+        members.iter().flat_map(|m| representatives.iter().map(move |r| Species::compatibility_distance(&m.genome, r))).sum()
+    });
+    println!("speedup {:.1}x", old.as_secs_f64() / new.as_secs_f64());
+
+    // a full round of speciation
+    let mut species = representatives.iter().enumerate().map(|(i, g)| Species::new(g, i)).collect::<Vec<_>>();
+    let mut counter = SpeciesCounter { id: SPECIES };
+    time("sort_species", || {
+        Species::sort_species(&mut species, members, &mut counter);
+        species.len() as f64
+    });
+}
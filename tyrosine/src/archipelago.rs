@@ -0,0 +1,171 @@
+use std::{collections::HashMap, fs::File, io::{self, BufReader, BufWriter, Write}, mem, path::Path};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use crate::{config::Config, genome::{Genome, GlobalInnovator}, hall_of_fame::HallOfFameEntry, population::Population};
+
+
+
+/// Which islands send their best genomes to which
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MigrationTopology {
+    #[default]
+    Ring, //every island sends to the next one, the last sends to the first
+    FullyConnected, //every island sends to every other island
+    Random, //every island sends to one other island picked at random each migration
+}
+
+
+
+/// Several populations evolved independently with occasional migration between them, all sharing one innovation history
+#[derive(Serialize, Deserialize)]
+pub struct Archipelago {
+    innovator: GlobalInnovator, //shared by every island
+    islands: Vec<Population>,
+    pub migration_interval: usize, //generations between migrations, 0 never migrates
+    pub migrants: usize, //fittest genomes every island sends along each route, an island takes in fewer than its size
+    pub topology: MigrationTopology,
+}
+impl Archipelago {
+    /// Create islands of the same size, each with a copy of the config
+    pub fn new(num_inputs: usize, num_outputs: usize, island_count: usize, island_size: usize, config: Config) -> Self {
+        assert_ne!(island_count, 0, "Archipelago has at least 1 island.");
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new(); //the same first connections get the same innov numbers on every island
+        let islands = (0..island_count)
+            .map(|_| {
+                let mut island = Population::with_innovator(num_inputs, num_outputs, island_size, config.clone(), mem::take(&mut innovator), &mut innovations);
+                mem::swap(&mut innovator, &mut island.innovator); //take the history back for the next island
+                island
+            })
+            .collect();
+        Archipelago {
+            innovator,
+            islands,
+            migration_interval: 10,
+            migrants: 2,
+            topology: MigrationTopology::default(),
+        }
+    }
+
+
+    /// Every island, in the order fitnesses are expected
+    pub fn islands(&self) -> &[Population] {
+        &self.islands
+    }
+
+
+    /// One island, for registering reporters or activating members
+    /// Evolve it through the archipelago only, or its innovation numbers will clash with the other islands
+    pub fn island_mut(&mut self, index: usize) -> &mut Population {
+        &mut self.islands[index]
+    }
+
+
+    /// The number of generations evolved so far
+    pub fn generation(&self) -> usize {
+        self.islands[0].generation()
+    }
+
+
+    /// The best genome ever evaluated on any island
+    pub fn champion(&self) -> Option<&HallOfFameEntry> {
+        self.islands.iter()
+            .filter_map(|i| i.champion())
+            .max_by(|x, y| x.fitness.partial_cmp(&y.fitness).unwrap_or(std::cmp::Ordering::Less))
+    }
+
+
+    /// Evolve every island with its own fitnesses, in the same order as its activate_index
+    /// Every migration_interval generations the fittest genomes are sent along the topology to the new generations
+    pub fn evolve(&mut self, fitnesses: &[Vec<f64>]) {
+        assert_eq!(fitnesses.len(), self.islands.len(), "One fitness list per island.");
+        let migrating = self.islands.len() > 1
+            && self.migration_interval > 0
+            && (self.generation() + 1).is_multiple_of(self.migration_interval);
+
+        // pick the emigrants while the fitnesses still match the members
+        let emigrants = if migrating {
+            self.islands.iter().zip(fitnesses).map(|(island, f)| Archipelago::fittest(island, f, self.migrants)).collect()
+        } else {
+            vec![]
+        };
+
+        let mut innovations = HashMap::new(); //the same structure added this generation gets the same innov number on every island
+        for (island, f) in self.islands.iter_mut().zip(fitnesses) {
+            mem::swap(&mut self.innovator, &mut island.innovator);
+            island.evolve_sharing(f, &mut innovations);
+            mem::swap(&mut self.innovator, &mut island.innovator);
+        }
+
+        if migrating {
+            let mut incoming = vec![vec![]; self.islands.len()];
+            for (from, to) in self.routes() {
+                incoming[to].extend(emigrants[from].iter().cloned());
+            }
+            let mut rng = rand::rng();
+            for (island, mut genomes) in self.islands.iter_mut().zip(incoming) {
+                genomes.shuffle(&mut rng); //no sender is favoured when there are too many
+                genomes.truncate(island.population_size.saturating_sub(1)); //every island keeps at least one of its own
+                island.immigrate(&genomes);
+            }
+        }
+    }
+
+
+    /// Notify every island's reporters that the run is over
+    pub fn end_run(&mut self) {
+        for island in &mut self.islands {
+            island.end_run();
+        }
+    }
+
+
+    /// The fittest genomes of an island, best first
+    fn fittest(island: &Population, fitnesses: &[f64], count: usize) -> Vec<Genome> {
+        let mut members = island.species.iter()
+            .flat_map(|s| &s.members)
+            .zip(fitnesses)
+            .collect::<Vec<_>>();
+        members.sort_by(|x, y| y.1.partial_cmp(x.1).unwrap_or(std::cmp::Ordering::Less));
+        members.into_iter().take(count).map(|(p, _)| p.genome.clone()).collect()
+    }
+
+
+    /// Pairs of sending and receiving island indices for one migration
+    fn routes(&self) -> Vec<(usize, usize)> {
+        let n = self.islands.len();
+        match self.topology {
+            MigrationTopology::Ring => (0..n).map(|i| (i, (i + 1) % n)).collect(),
+            MigrationTopology::FullyConnected => (0..n)
+                .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+                .collect(),
+            MigrationTopology::Random => {
+                let mut rng = rand::rng();
+                (0..n).map(|i| (i, (i + rng.random_range(1..n)) % n)).collect() //never itself
+            },
+        }
+    }
+
+
+    /// Save every island and the shared history to one JSON file, reporters aren't saved
+    /// Fails if any island's config has something custom that can't be saved
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+
+
+    /// Load an archipelago previously written with save
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Archipelago> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut archipelago: Archipelago = serde_json::from_reader(reader)?;
+        if archipelago.islands.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "an archipelago has at least 1 island"));
+        }
+        for island in &mut archipelago.islands {
+            island.restore()?;
+        }
+        Ok(archipelago)
+    }
+}
@@ -0,0 +1,447 @@
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+use crate::{genome::{Aggregation, Genome, NodeKind}, mutation::MutationOperators, speciation::SpeciationStrategy, species::SMALL_GENOME_SIZE};
+
+
+
+/// Tunable parameters of a Population, defaults follow the original NEAT paper where possible
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub hall_of_fame_size: usize, //number of distinct genomes kept in the hall of fame
+    pub fitness_sharing: bool, //divide each member's fitness by its species size and select parents by it
+    pub young_species_age: usize, //species younger than this many generations get the young fitness boost
+    pub young_fitness_boost: f64, //multiplier on the fitness of young species, 1.0 disables
+    pub old_species_age: usize, //species at least this many generations old get the old fitness penalty
+    pub old_fitness_penalty: f64, //multiplier on the fitness of old species, 1.0 disables
+    pub protected_species_age: usize, //species younger than this are guaranteed protected_species_slots, 0 disables
+    pub protected_species_slots: usize, //minimum reproductive slots for protected species
+    pub node_bias: bool, //give hidden and output nodes their own evolvable bias
+    pub aggregations: Vec<Aggregation>, //aggregation functions a node can mutate to
+    pub initial_topology: InitialTopology, //structure every genome starts with
+    pub weight_init: WeightInit, //how new connections get their first weight
+    pub weight_clamp: Option<(f64, f64)>, //(min, max) every weight is kept within, None leaves weights unbounded
+    pub mutation: MutationRates,
+    pub mutation_operators: MutationOperators, //built-in operators are saved with their weights, custom ones can't be
+    pub small_genome_size: usize, //genomes with fewer connection genes than this aren't normalized when measuring distance, 0 always normalizes and caps the structural terms at 2
+    pub speciation: SpeciationStrategy, //built-in strategies are saved, custom ones can't be
+    pub mutation_power_schedule: Schedule, //scales both perturbation stds over the run
+    pub structural_schedule: Schedule, //scales the add and remove rates over the run
+    pub self_adaptation: Option<SelfAdaptation>, //every genome carries and evolves its own mutation rates, None uses the config's
+    pub parsimony: Option<Parsimony>, //penalize fitness by network size before selection, None leaves fitness alone, evolve_multi ignores it
+    pub complexity_objective: bool, //add smaller networks as an extra objective in Population::evolve_multi
+    pub novelty: Option<NoveltySearch>, //settings for Population::evolve_with_behaviors
+    pub phased_search: Option<PhasedSearch>, //alternate between complexifying and simplifying, None always complexifies
+    pub track_lineage: bool, //remember every individual's parents and mutations, grows with every birth
+    pub track_species_history: bool, //remember every species' size, fitness and type specimen each generation
+}
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            hall_of_fame_size: 10,
+            fitness_sharing: false,
+            young_species_age: 10,
+            young_fitness_boost: 1.0,
+            old_species_age: 50,
+            old_fitness_penalty: 1.0,
+            protected_species_age: 0,
+            protected_species_slots: 2,
+            node_bias: false,
+            aggregations: Aggregation::ALL.to_vec(),
+            initial_topology: InitialTopology::default(),
+            weight_init: WeightInit::default(),
+            weight_clamp: None,
+            mutation: MutationRates::default(),
+            mutation_operators: MutationOperators::default(),
+            small_genome_size: SMALL_GENOME_SIZE,
+            speciation: SpeciationStrategy::default(),
+            mutation_power_schedule: Schedule::default(),
+            structural_schedule: Schedule::default(),
+            self_adaptation: None,
+            parsimony: None,
+            complexity_objective: false,
+            novelty: None,
+            phased_search: None,
+            track_lineage: false,
+            track_species_history: false,
+        }
+    }
+}
+
+
+impl Config {
+    /// Keep a weight within the configured clamp, if any
+    /// Inverted bounds are swapped and NaN bounds are ignored, so a hand-written config can't panic mid-evolution
+    pub fn clamp_weight(&self, weight: f64) -> f64 {
+        match self.weight_clamp {
+            Some((min, max)) => {
+                let min = if min.is_nan() { f64::NEG_INFINITY } else { min };
+                let max = if max.is_nan() { f64::INFINITY } else { max };
+                if min <= max { weight.clamp(min, max) } else { weight.clamp(max, min) }
+            },
+            None => weight,
+        }
+    }
+}
+
+
+
+/// Structure of the genomes a Population starts with, the bias counts as an input
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum InitialTopology {
+    #[default]
+    Minimal, //no connections, each genome is mutated once
+    FullyConnected, //every input connected to every output
+    Partial(f64), //each input to output connection exists with this probability
+    BiasOnly, //only the bias connected to every output
+    FullyConnectedHidden(usize), //every input connected to this many hidden nodes, which all connect to every output
+}
+
+
+
+/// Distribution the first weight of a new connection is drawn from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum WeightInit {
+    Constant(f64),
+    Uniform { min: f64, max: f64 },
+    Normal { mean: f64, std: f64 },
+    Xavier, //normal with a standard deviation of 1/sqrt(fan-in) of the receiving node
+}
+impl Default for WeightInit {
+    fn default() -> Self {
+        WeightInit::Constant(1.0)
+    }
+}
+impl WeightInit {
+    /// Draw a weight for a connection into a node with the given number of incoming connections
+    pub fn sample<R: Rng + ?Sized>(&self, fan_in: usize, rng: &mut R) -> f64 {
+        match *self {
+            WeightInit::Constant(weight) => weight,
+            WeightInit::Uniform { min, max } if min < max => rng.random_range(min..max),
+            WeightInit::Uniform { min, .. } => min, //empty range, nothing to pick from
+            WeightInit::Normal { mean, std } => Normal::new(mean, std).map_or(mean, |n| n.sample(rng)),
+            WeightInit::Xavier => {
+                let std = 1.0 / (fan_in.max(1) as f64).sqrt();
+                Normal::new(0.0, std).unwrap().sample(rng) //safe unwrap, std is always positive
+            },
+        }
+    }
+}
+
+
+
+/// Probabilities and step sizes used when mutating a genome
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct MutationRates {
+    pub add_connection: f64,
+    pub add_node: f64, //should be the least common mutation type
+    pub remove_connection: f64,
+    pub remove_node: f64, //only hidden nodes are ever removed
+    pub weight: f64, //chance of each weight being mutated
+    pub perturbation_chance: f64, //(1-perturbation_chance) is the chance of total replacement vs just a nudge
+    pub perturbation_std: f64,
+    pub replacement_range: f64,
+    pub toggle: f64,
+    pub bias: f64, //chance of each node bias being mutated, only used with node biases
+    pub bias_perturbation_chance: f64,
+    pub bias_perturbation_std: f64,
+    pub bias_replacement_range: f64,
+    pub aggregation: f64, //chance of each node switching aggregation function
+}
+impl Default for MutationRates {
+    fn default() -> Self {
+        MutationRates {
+            add_connection: 0.15,
+            add_node: 0.03,
+            remove_connection: 0.0,
+            remove_node: 0.0,
+            weight: 0.8,
+            perturbation_chance: 0.9,
+            perturbation_std: 0.1,
+            replacement_range: 5.0,
+            toggle: 0.01,
+            bias: 0.7,
+            bias_perturbation_chance: 0.9,
+            bias_perturbation_std: 0.1,
+            bias_replacement_range: 1.0,
+            aggregation: 0.0,
+        }
+    }
+}
+
+
+
+impl MutationRates {
+    /// Average of the adaptive rates, the rest are left at their defaults, None if there's nothing to average
+    pub fn mean<I: IntoIterator<Item = MutationRates>>(all: I) -> Option<MutationRates> {
+        let mut count = 0;
+        let mut sum = MutationRates {
+            add_connection: 0.0,
+            add_node: 0.0,
+            remove_connection: 0.0,
+            remove_node: 0.0,
+            weight: 0.0,
+            toggle: 0.0,
+            perturbation_std: 0.0,
+            ..MutationRates::default()
+        };
+        for rates in all {
+            count += 1;
+            sum.add_connection += rates.add_connection;
+            sum.add_node += rates.add_node;
+            sum.remove_connection += rates.remove_connection;
+            sum.remove_node += rates.remove_node;
+            sum.weight += rates.weight;
+            sum.toggle += rates.toggle;
+            sum.perturbation_std += rates.perturbation_std;
+        }
+        if count == 0 {
+            return None;
+        }
+        let n = count as f64;
+        Some(MutationRates {
+            add_connection: sum.add_connection / n,
+            add_node: sum.add_node / n,
+            remove_connection: sum.remove_connection / n,
+            remove_node: sum.remove_node / n,
+            weight: sum.weight / n,
+            toggle: sum.toggle / n,
+            perturbation_std: sum.perturbation_std / n,
+            ..sum
+        })
+    }
+}
+
+
+
+/// Settings for genomes that evolve their own mutation rates
+/// Adaptive rates are the structural rates, the weight and toggle chances and the perturbation std
+/// Genomes evolve multipliers on the rates of the current generation, so phased search and schedules still apply
+/// A rate that's 0 this generation stays 0 whatever the genome's multiplier
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SelfAdaptation {
+    pub learning_rate: f64, //tau, the std of the log-normal step
+    pub min_rate: f64, //floor so an enabled rate can't get stuck near 0
+    pub max_perturbation_std: f64,
+    pub max_factor: f64, //multipliers stay within 1/max_factor and max_factor
+}
+impl Default for SelfAdaptation {
+    fn default() -> Self {
+        SelfAdaptation {
+            learning_rate: 0.2,
+            min_rate: 0.001,
+            max_perturbation_std: 2.0,
+            max_factor: 10.0,
+        }
+    }
+}
+impl SelfAdaptation {
+    /// Panic on settings that can't be adapted with, checked when a population or archive is created
+    pub fn assert_valid(&self) {
+        assert!(self.learning_rate >= 0.0, "Self-adaptation learning rate is not negative.");
+        assert!(self.min_rate >= 0.0 && self.min_rate <= 1.0, "Self-adaptation min rate is between 0 and 1.");
+        assert!(self.max_perturbation_std >= self.min_rate, "Self-adaptation max perturbation std is at least the min rate.");
+        assert!(self.max_factor >= 1.0, "Self-adaptation max factor is at least 1.");
+    }
+}
+
+
+
+/// Multipliers a self-adaptive genome carries for each adaptive rate, every one starts at 1
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RateFactors {
+    pub add_connection: f64,
+    pub add_node: f64,
+    pub remove_connection: f64,
+    pub remove_node: f64,
+    pub weight: f64,
+    pub toggle: f64,
+    pub perturbation_std: f64,
+}
+impl Default for RateFactors {
+    fn default() -> Self {
+        RateFactors {
+            add_connection: 1.0,
+            add_node: 1.0,
+            remove_connection: 1.0,
+            remove_node: 1.0,
+            weight: 1.0,
+            toggle: 1.0,
+            perturbation_std: 1.0,
+        }
+    }
+}
+impl RateFactors {
+    /// Log-normal self-adaptation in the style of evolution strategies: factor * exp(tau * N(0, 1))
+    pub fn adapt<R: Rng + ?Sized>(&mut self, adaptation: &SelfAdaptation, rng: &mut R) {
+        let normal = Normal::new(0.0, adaptation.learning_rate.max(0.0)).unwrap(); //safe unwrap, std is never negative
+        let max = adaptation.max_factor.max(1.0);
+        for factor in [
+            &mut self.add_connection,
+            &mut self.add_node,
+            &mut self.remove_connection,
+            &mut self.remove_node,
+            &mut self.weight,
+            &mut self.toggle,
+            &mut self.perturbation_std,
+        ] {
+            *factor = (*factor * normal.sample(rng).exp()).clamp(1.0 / max, max);
+        }
+    }
+
+
+    /// Each factor comes from either parent at random
+    pub fn crossover(a: &RateFactors, b: &RateFactors) -> RateFactors {
+        let pick = |x: f64, y: f64| if rand::random() { x } else { y };
+        RateFactors {
+            add_connection: pick(a.add_connection, b.add_connection),
+            add_node: pick(a.add_node, b.add_node),
+            remove_connection: pick(a.remove_connection, b.remove_connection),
+            remove_node: pick(a.remove_node, b.remove_node),
+            weight: pick(a.weight, b.weight),
+            toggle: pick(a.toggle, b.toggle),
+            perturbation_std: pick(a.perturbation_std, b.perturbation_std),
+        }
+    }
+
+
+    /// The rates a genome mutates with, scaling this generation's rates by its factors
+    /// Enabled rates stay between min_rate and their maximum, disabled ones stay at 0
+    pub fn apply(&self, rates: &MutationRates, adaptation: &SelfAdaptation) -> MutationRates {
+        let scale = |rate: f64, factor: f64, max: f64| {
+            if rate > 0.0 {
+                (rate * factor).max(adaptation.min_rate).min(max)
+            } else {
+                rate
+            }
+        };
+        MutationRates {
+            add_connection: scale(rates.add_connection, self.add_connection, 1.0),
+            add_node: scale(rates.add_node, self.add_node, 1.0),
+            remove_connection: scale(rates.remove_connection, self.remove_connection, 1.0),
+            remove_node: scale(rates.remove_node, self.remove_node, 1.0),
+            weight: scale(rates.weight, self.weight, 1.0),
+            toggle: scale(rates.toggle, self.toggle, 1.0),
+            perturbation_std: scale(rates.perturbation_std, self.perturbation_std, adaptation.max_perturbation_std),
+            ..*rates
+        }
+    }
+}
+
+
+
+/// How a group of mutation rates is scaled as the run goes on
+/// Self-adaptive genomes scale the scheduled rates by their own factors
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Schedule {
+    #[default]
+    Constant,
+    Linear { start: f64, end: f64, generations: usize }, //moves from start to end over the generations, then stays at end
+    Exponential { start: f64, decay: f64, min: f64 }, //start * decay^generation, never below min
+    StagnationBoost { stall_generations: usize, boost: f64 }, //multiply by boost while the best fitness hasn't improved for this long
+}
+impl Schedule {
+    /// Multiplier for the given generation, stagnant_generations counts generations without a new best fitness
+    pub fn multiplier(&self, generation: usize, stagnant_generations: usize) -> f64 {
+        match *self {
+            Schedule::Constant => 1.0,
+            Schedule::Linear { start, end, generations } => {
+                let progress = (generation as f64 / generations.max(1) as f64).min(1.0);
+                start + (end - start) * progress
+            },
+            Schedule::Exponential { start, decay, min } => (start * decay.powf(generation as f64)).max(min),
+            Schedule::StagnationBoost { stall_generations, boost } => {
+                if stagnant_generations >= stall_generations { boost } else { 1.0 }
+            },
+        }
+    }
+}
+
+
+
+/// Parsimony pressure, subtracts a penalty for network size from fitness before selection
+/// Only what's beyond the free counts is penalized, leave them at 0 for a linear penalty
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Parsimony {
+    pub connection_penalty: f64, //per enabled connection, 0 ignores connections
+    pub node_penalty: f64, //per hidden node, 0 ignores nodes
+    pub free_connections: usize,
+    pub free_nodes: usize,
+}
+impl Default for Parsimony {
+    fn default() -> Self {
+        Parsimony {
+            connection_penalty: 0.01,
+            node_penalty: 0.0,
+            free_connections: 0,
+            free_nodes: 0,
+        }
+    }
+}
+impl Parsimony {
+    /// How much fitness a genome loses for its size
+    pub fn penalty(&self, genome: &Genome) -> f64 {
+        let connections = genome.connection_genes.iter().filter(|c| c.enabled).count();
+        let nodes = genome.node_genes.iter().filter(|n| genome.node_kind(n.id) == NodeKind::Hidden).count();
+        self.connection_penalty * connections.saturating_sub(self.free_connections) as f64 +
+        self.node_penalty * nodes.saturating_sub(self.free_nodes) as f64
+    }
+}
+
+
+
+/// Settings for novelty search, which rewards behaving unlike the population and the archive
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct NoveltySearch {
+    pub k: usize, //nearest neighbours averaged for sparseness
+    pub archive_threshold: f64, //sparseness needed to enter the archive
+    pub archive_chance: f64, //chance of archiving any behavior regardless of sparseness
+    pub archive_capacity: usize, //oldest behaviors are forgotten past this, 0 never forgets
+    pub novelty_weight: f64, //1 selects on novelty alone, 0 on fitness alone, anything between blends them
+}
+impl Default for NoveltySearch {
+    fn default() -> Self {
+        NoveltySearch {
+            k: 15,
+            archive_threshold: 1.0,
+            archive_chance: 0.0,
+            archive_capacity: 1000,
+            novelty_weight: 1.0,
+        }
+    }
+}
+
+
+
+/// Phased search alternates between growing and shrinking genomes, based on the population's mean complexity
+/// Complexity is the number of enabled connections plus hidden nodes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PhasedSearch {
+    pub complexity_threshold: f64, //start simplifying once mean complexity grows this far past the last floor
+    pub stall_generations: usize, //go back to complexifying once mean complexity hasn't dropped for this long
+    pub remove_connection: f64, //removal rates used while simplifying, addition rates are 0 then
+    pub remove_node: f64,
+}
+impl Default for PhasedSearch {
+    fn default() -> Self {
+        PhasedSearch {
+            complexity_threshold: 30.0,
+            stall_generations: 10,
+            remove_connection: 0.15,
+            remove_node: 0.03,
+        }
+    }
+}
+
+
+
+/// The current direction of a phased search
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchPhase {
+    #[default]
+    Complexifying,
+    Simplifying,
+}
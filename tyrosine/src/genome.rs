@@ -1,7 +1,8 @@
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use rand::{seq::{IndexedMutRandom, IndexedRandom}, Rng};
 use serde::{Serialize, Deserialize};
 use rand_distr::{Distribution, Normal};
+use crate::{config::{Config, InitialTopology, MutationRates, RateFactors}, mutation::MutationContext};
 
 
 
@@ -9,12 +10,18 @@ use rand_distr::{Distribution, Normal};
 pub struct GlobalInnovator {
     pub innov: usize,
 }
+impl Default for GlobalInnovator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl GlobalInnovator {
     pub fn new() -> Self {
         GlobalInnovator { innov: 0 }
     }
 
     /// Get the next innov number and increment internally
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> usize {
         let innov = self.innov;
         self.innov += 1;
@@ -24,9 +31,71 @@ impl GlobalInnovator {
 
 
 
+/// The role a node plays in the network, derived from its id
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Bias,
+    Input,
+    Output,
+    Hidden,
+}
+
+
+
+/// How a node combines its weighted inputs before activation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregation {
+    #[default]
+    Sum,
+    Product,
+    Max,
+    Min,
+    Mean,
+    Median,
+}
+impl Aggregation {
+    pub const ALL: [Aggregation; 6] = [
+        Aggregation::Sum,
+        Aggregation::Product,
+        Aggregation::Max,
+        Aggregation::Min,
+        Aggregation::Mean,
+        Aggregation::Median,
+    ];
+
+    /// Combine weighted inputs, a node without inputs always aggregates to 0
+    pub fn aggregate(&self, values: &mut [f64]) -> f64 {
+        if values.is_empty() {
+            return 0.0;
+        }
+        match self {
+            Aggregation::Sum => values.iter().sum(),
+            Aggregation::Product => values.iter().product(),
+            Aggregation::Max => values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            Aggregation::Min => values.iter().cloned().fold(f64::INFINITY, f64::min),
+            Aggregation::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregation::Median => {
+                values.sort_by(|a, b| a.total_cmp(b));
+                let mid = values.len() / 2;
+                if values.len().is_multiple_of(2) {
+                    (values[mid - 1] + values[mid]) / 2.0
+                } else {
+                    values[mid]
+                }
+            },
+        }
+    }
+}
+
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct NodeGene {
     pub id: usize,
+    #[serde(default)]
+    pub bias: f64, //added before activation, stays 0 unless node biases are enabled
+    #[serde(default)]
+    pub aggregation: Aggregation,
 }
 
 
@@ -48,25 +117,29 @@ impl ConnectionGene {
 
 
 
+/// Everything Genome::pruned removed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub removed_nodes: Vec<usize>, //ids
+    pub removed_connections: Vec<usize>, //innov numbers
+}
+
+
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Genome {
     pub num_inputs: usize, //includes bias
     pub num_outputs: usize,
     pub node_genes: Vec<NodeGene>,
     pub connection_genes: Vec<ConnectionGene>,
+    #[serde(default)]
+    pub rate_factors: Option<RateFactors>, //own multipliers on the config's rates, only used with self-adaptive mutation
 }
-const CONNECTION_MUTATION_RATE: f64 = 0.15;
-const NODE_MUTATION_RATE: f64 = 0.03; //should be the least common mutation type
-const WEIGHT_MUTATION_RATE: f64 = 0.8;
-const PERTUBATION_CHANCE: f64 = 0.9; //(1-pertubation_chance) is the chance of total replacement vs just a nudge
-const PERTUBATION_STD: f64 = 0.1;
-const REPLACEMENT_RANGE: f64 = 5.0;
-const TOGGLE_MUTATION_RATE: f64 = 0.01;
 impl Genome {
     /// Create a new genome with the specified number of inputs and outputs
     pub fn new(num_inputs: usize, num_outputs: usize) -> Self {
-        let node_genes = (0..(num_inputs + 1 + num_outputs)).into_iter()
-            .map(|i| NodeGene { id: i})
+        let node_genes = (0..(num_inputs + 1 + num_outputs))
+            .map(|i| NodeGene { id: i, bias: 0.0, aggregation: Aggregation::Sum })
             .collect();
 
         Genome {
@@ -74,6 +147,66 @@ impl Genome {
             num_outputs,
             node_genes,
             connection_genes: Vec::new(),
+            rate_factors: None,
+        }
+    }
+
+
+    /// Create a new genome already wired up with the given topology, weights come from the configured initialisation
+    /// Every weight is then perturbed once, so genomes wired from a constant initialisation still differ
+    /// Shares innovation numbers through innovations so identical structures line up across the population
+    pub fn with_topology(num_inputs: usize, num_outputs: usize, topology: InitialTopology, config: &Config, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) -> Self {
+        let mut genome = Genome::new(num_inputs, num_outputs);
+        let mut rng = rand::rng();
+        let inputs = 0..genome.num_inputs;
+        let outputs = genome.num_inputs..(genome.num_inputs + genome.num_outputs);
+
+        let pairs: Vec<(usize, usize)> = match topology {
+            InitialTopology::Minimal => vec![],
+            InitialTopology::FullyConnected => inputs.flat_map(|i| outputs.clone().map(move |o| (i, o))).collect(),
+            InitialTopology::Partial(p) => inputs.flat_map(|i| outputs.clone().map(move |o| (i, o)))
+                .filter(|_| rng.random::<f64>() < p)
+                .collect(),
+            InitialTopology::BiasOnly => outputs.map(|o| (0, o)).collect(),
+            InitialTopology::FullyConnectedHidden(n) => {
+                let first_hidden = outputs.end;
+                let hidden = first_hidden..(first_hidden + n);
+                genome.node_genes.extend(hidden.clone().map(|id| NodeGene { id, bias: 0.0, aggregation: Aggregation::Sum }));
+                let into_hidden = inputs.flat_map(|i| hidden.clone().map(move |h| (i, h)));
+                let out_of_hidden = hidden.clone().flat_map(|h| outputs.clone().map(move |o| (h, o)));
+                into_hidden.chain(out_of_hidden).collect()
+            },
+        };
+
+        for (in_node, out_node) in pairs {
+            let innov = *innovations.entry((in_node, out_node)).or_insert_with(|| innovator.next());
+            let fan_in = 1 + genome.connection_genes.iter().filter(|c| c.out_node == out_node).count();
+            genome.connection_genes.push(ConnectionGene {
+                in_node,
+                out_node,
+                weight: config.clamp_weight(config.weight_init.sample(fan_in, &mut rng)),
+                enabled: true,
+                innov,
+            });
+        }
+
+        genome.connection_genes.sort_by_key(|c| c.innov);
+        let perturb_all = MutationRates { weight: 1.0, perturbation_chance: 1.0, ..config.mutation };
+        genome.mutate_weights(config, &perturb_all);
+        genome
+    }
+
+
+    /// Determine the role of a node, ids are laid out as bias, inputs, outputs, then hidden
+    pub fn node_kind(&self, id: usize) -> NodeKind {
+        if id == 0 {
+            NodeKind::Bias
+        } else if id < self.num_inputs {
+            NodeKind::Input
+        } else if id < self.num_inputs + self.num_outputs {
+            NodeKind::Output
+        } else {
+            NodeKind::Hidden
         }
     }
 
@@ -99,11 +232,11 @@ impl Genome {
             match (fitter_map.get(&innov), unfit_map.get(&innov)) {
                 (Some(&a), Some(&b)) => {
                     // matching gene: pick randomly
-                    child_connections.push(if rand::random() { a.clone() } else { b.clone() });
+                    child_connections.push(if rand::random() { *a } else { *b });
                 }
                 (Some(&a), None) => {
                     // excess: take from fitter
-                    child_connections.push(a.clone());
+                    child_connections.push(*a);
                 }
                 (None, Some(_b)) => {
                     // gene only in less-fit: ignore
@@ -112,62 +245,133 @@ impl Genome {
             }
         }
 
+        // fitter parent has same structure, guaranteed to have the same nodes, but matching node attributes are picked randomly
+        let unfit_nodes: HashMap<usize, &NodeGene> = unfit_parent.node_genes.iter().map(|n| (n.id, n)).collect();
+        let child_nodes = fit_parent.node_genes.iter()
+            .map(|n| match unfit_nodes.get(&n.id) {
+                Some(&other) if rand::random() => *other,
+                _ => *n,
+            })
+            .collect();
+
         Genome {
             num_inputs: fit_parent.num_inputs,
             num_outputs: fit_parent.num_outputs,
-            node_genes: fit_parent.node_genes.clone(), //fitter parent has same structure, guaranteed to have the same nodes
+            node_genes: child_nodes,
             connection_genes: child_connections,
+            rate_factors: match (fit_parent.rate_factors, unfit_parent.rate_factors) {
+                (Some(a), Some(b)) => Some(RateFactors::crossover(&a, &b)),
+                (a, b) => a.or(b),
+            },
         }
     }
 
 
-    /// Master mutate function, calls the other mutate functions
+    /// Master mutate function, applies every registered mutation operator in order
     /// NOTE: no guarantee that the genome produced is valid
-    pub fn mutate(&mut self, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) {
+    /// Returns the names of the operators that were applied
+    pub fn mutate(&mut self, config: &Config, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) -> Vec<String> {
         let mut rng = rand::rng();
 
-        self.mutate_weights_and_toggle();
-
-        if rng.random::<f64>() < CONNECTION_MUTATION_RATE {
-            self.add_connection(innovator, innovations);
-        }
+        // self-adaptive genomes mutate their own factors first, then scale this generation's rates with them
+        let rates = match &config.self_adaptation {
+            Some(adaptation) => {
+                let factors = self.rate_factors.get_or_insert_with(RateFactors::default);
+                factors.adapt(adaptation, &mut rng);
+                factors.apply(&config.mutation, adaptation)
+            },
+            None => config.mutation,
+        };
+        let mut context = MutationContext { config, rates, innovator, innovations };
 
-        if rng.random::<f64>() < NODE_MUTATION_RATE {
-            self.add_node(innovator, innovations);
+        let mut applied = vec![];
+        for (weight, operator) in config.mutation_operators.iter() {
+            if rng.random::<f64>() < weight * operator.rate(&context.rates) {
+                operator.mutate(self, &mut context);
+                applied.push(operator.name().to_string());
+            }
         }
+        self.connection_genes.sort_by_key(|c| c.innov); //custom operators may leave genes out of order, distance relies on it
+        applied
     }
 
 
     /// Apply mutations to internal weights
-    fn mutate_weights_and_toggle(&mut self) {
+    pub(crate) fn mutate_weights(&mut self, config: &Config, rates: &MutationRates) {
         let mut rng = rand::rng();
-        let normal = Normal::new(0.0, PERTUBATION_STD).unwrap(); //probably safe unwrap
+        let normal = Normal::new(0.0, rates.perturbation_std).unwrap(); //probably safe unwrap
 
         for connection in &mut self.connection_genes {
-            if rng.random::<f64>() < TOGGLE_MUTATION_RATE {
-                connection.enabled = !connection.enabled;
-            }
-
-            if rng.random::<f64>() > WEIGHT_MUTATION_RATE { //small chance we don't mutate
+            if rng.random::<f64>() > rates.weight { //small chance we don't mutate
                 continue;
             }
 
-            if rng.random::<f64>() < PERTUBATION_CHANCE {
+            if rng.random::<f64>() < rates.perturbation_chance {
                 // pertubate the weight
                 let pertub_amount = normal.sample(&mut rng);
-                connection.weight += pertub_amount;
+                connection.weight = config.clamp_weight(connection.weight + pertub_amount);
             } else {
                 // replace the weight
-                let new_weight = rng.random_range(-REPLACEMENT_RANGE..REPLACEMENT_RANGE);
-                connection.weight = new_weight;
+                let new_weight = rng.random_range(-rates.replacement_range..rates.replacement_range);
+                connection.weight = config.clamp_weight(new_weight);
+            }
+        }
+    }
+
+
+    /// Flip connections between enabled and disabled
+    pub(crate) fn toggle_connections(&mut self, rates: &MutationRates) {
+        let mut rng = rand::rng();
+        for connection in &mut self.connection_genes {
+            if rng.random::<f64>() < rates.toggle {
+                connection.enabled = !connection.enabled;
+            }
+        }
+    }
+
+
+    /// Switch node aggregation functions, inputs never aggregate anything
+    pub(crate) fn mutate_aggregations(&mut self, config: &Config, rates: &MutationRates) {
+        if config.aggregations.is_empty() {
+            return;
+        }
+
+        let mut rng = rand::rng();
+        let num_inputs = self.num_inputs;
+        for node in self.node_genes.iter_mut().filter(|n| n.id >= num_inputs) {
+            if rng.random::<f64>() < rates.aggregation {
+                node.aggregation = *config.aggregations.choose(&mut rng).unwrap(); //safe unwrap, checked above
+            }
+        }
+    }
+
+
+    /// Biases get their own nudges and replacements, inputs never use theirs
+    pub(crate) fn mutate_biases(&mut self, config: &Config, rates: &MutationRates) {
+        if !config.node_bias {
+            return;
+        }
+
+        let mut rng = rand::rng();
+        let bias_normal = Normal::new(0.0, rates.bias_perturbation_std).unwrap(); //probably safe unwrap
+        let num_inputs = self.num_inputs;
+        for node in self.node_genes.iter_mut().filter(|n| n.id >= num_inputs) {
+            if rng.random::<f64>() > rates.bias {
+                continue;
+            }
+
+            if rng.random::<f64>() < rates.bias_perturbation_chance {
+                node.bias += bias_normal.sample(&mut rng);
+            } else {
+                node.bias = rng.random_range(-rates.bias_replacement_range..rates.bias_replacement_range);
             }
         }
     }
 
 
     /// A type of mutation, chooses one connection to split up
-    fn add_node(&mut self, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) {
-        if self.connection_genes.len() < 1 {
+    pub(crate) fn add_node(&mut self, config: &Config, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) {
+        if self.connection_genes.is_empty() {
             return;
         }
 
@@ -178,11 +382,13 @@ impl Genome {
             .collect::<Vec<&mut ConnectionGene>>();
 
         // disable the existing node
-        let chosen = collected.choose_mut(&mut rng).unwrap(); //safe unwrap
+        let Some(chosen) = collected.choose_mut(&mut rng) else {
+            return; //every connection is disabled, which removals make possible
+        };
 
         // create a new node
         let new_id = self.node_genes.last().unwrap().id + 1;
-        self.node_genes.push(NodeGene { id: new_id});
+        self.node_genes.push(NodeGene { id: new_id, bias: 0.0, aggregation: Aggregation::Sum }); //no bias and a lone input so the split connection still functions the same
 
         // ensure we reuse innov numbers and remember any new innovations
         let innov0;
@@ -217,14 +423,14 @@ impl Genome {
         let connection_1 = ConnectionGene {
             in_node: new_id,
             out_node: chosen.out_node,
-            weight: 1.0, //ensure the other connection still functions like the old connection
+            weight: config.clamp_weight(1.0), //ensure the other connection still functions like the old connection
             enabled: true,
             innov: innov1,
         };
 
         // disable and modify old connection
         chosen.enabled = false;
-        chosen.weight = 1.0;
+        chosen.weight = config.clamp_weight(1.0);
 
         // push to genome
         self.connection_genes.push(connection_0);
@@ -237,7 +443,7 @@ impl Genome {
 
 
     /// A type of mutation, finds two unconnected nodes and adds a connection
-    fn add_connection(&mut self, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) {
+    pub(crate) fn add_connection(&mut self, config: &Config, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) {
         if self.node_genes.len() < 2 {
             return;
         }
@@ -285,7 +491,7 @@ impl Genome {
         //
         // worst case scenario add a check at the top of the function, i just
         // don't wanna do the math
-        if candidates.len() == 0 {
+        if candidates.is_empty() {
             return;
         }
 
@@ -305,10 +511,16 @@ impl Genome {
             },
         }
 
+        // fan-in counts the new connection too
+        let fan_in = 1 + self.connection_genes.iter()
+            .filter(|c| c.enabled && c.out_node == chosen.1)
+            .count();
+        let weight = config.clamp_weight(config.weight_init.sample(fan_in, &mut rng));
+
         self.connection_genes.push(ConnectionGene {
             in_node: chosen.0,
             out_node: chosen.1,
-            weight: 1.0,
+            weight,
             enabled: true,
             innov,
         });
@@ -316,6 +528,97 @@ impl Genome {
         // sort the connection genes by innov number
         self.connection_genes.sort_by_key(|c| c.innov);
     }
-}
 
 
+    /// A type of mutation, deletes one connection and any hidden node left without connections
+    pub(crate) fn remove_connection(&mut self) {
+        if self.connection_genes.is_empty() {
+            return;
+        }
+
+        let mut rng = rand::rng();
+        let chosen = rng.random_range(0..self.connection_genes.len());
+        let removed = self.connection_genes.remove(chosen);
+
+        // a hidden node with nothing left attached is dead weight
+        for id in [removed.in_node, removed.out_node] {
+            if self.node_kind(id) == NodeKind::Hidden && !self.connection_genes.iter().any(|c| c.in_node == id || c.out_node == id) {
+                self.node_genes.retain(|n| n.id != id);
+            }
+        }
+    }
+
+
+    /// A type of mutation, deletes one hidden node along with all of its connections
+    pub(crate) fn remove_node(&mut self) {
+        let mut rng = rand::rng();
+        let hidden = self.node_genes.iter()
+            .map(|n| n.id)
+            .filter(|&id| self.node_kind(id) == NodeKind::Hidden)
+            .collect::<Vec<usize>>();
+        let Some(&chosen) = hidden.choose(&mut rng) else {
+            return; //only inputs and outputs, nothing to remove
+        };
+
+        self.node_genes.retain(|n| n.id != chosen);
+        self.connection_genes.retain(|c| c.in_node != chosen && c.out_node != chosen);
+    }
+
+
+    /// Number of enabled connections plus hidden nodes, used to measure bloat
+    pub fn complexity(&self) -> usize {
+        let hidden = self.node_genes.len() - self.num_inputs - self.num_outputs;
+        hidden + self.connection_genes.iter().filter(|c| c.enabled).count()
+    }
+
+
+    /// Ids of every node with an enabled path to an output, outputs included
+    pub fn nodes_reaching_outputs(&self) -> HashSet<usize> {
+        let mut incoming: HashMap<usize, Vec<usize>> = HashMap::new();
+        for conn in self.connection_genes.iter().filter(|c| c.enabled) {
+            incoming.entry(conn.out_node).or_default().push(conn.in_node);
+        }
+
+        // walk backwards from the outputs
+        let mut frontier = self.node_genes.iter()
+            .map(|n| n.id)
+            .filter(|&id| self.node_kind(id) == NodeKind::Output)
+            .collect::<Vec<usize>>();
+        let mut reached: HashSet<usize> = frontier.iter().cloned().collect();
+        while let Some(node) = frontier.pop() {
+            for &input in incoming.get(&node).map(|v| v.as_slice()).unwrap_or(&[]) {
+                if reached.insert(input) {
+                    frontier.push(input);
+                }
+            }
+        }
+        reached
+    }
+
+
+    /// A functionally equivalent genome without disabled connections or hidden nodes that never reach an output
+    pub fn pruned(&self) -> (Genome, PruneReport) {
+        let reaching = self.nodes_reaching_outputs();
+        let mut report = PruneReport::default();
+        let mut genome = self.clone();
+
+        genome.node_genes.retain(|n| {
+            let keep = self.node_kind(n.id) != NodeKind::Hidden || reaching.contains(&n.id);
+            if !keep {
+                report.removed_nodes.push(n.id);
+            }
+            keep
+        });
+
+        // anything disabled or leading away from the outputs can't affect them
+        genome.connection_genes.retain(|c| {
+            let keep = c.enabled && reaching.contains(&c.out_node);
+            if !keep {
+                report.removed_connections.push(c.innov);
+            }
+            keep
+        });
+
+        (genome, report)
+    }
+}
@@ -0,0 +1,87 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+use serde::{Deserialize, Serialize};
+use crate::genome::Genome;
+
+
+
+/// A genome remembered by the hall of fame
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HallOfFameEntry {
+    pub genome: Genome,
+    pub fitness: f64,
+    pub generation: usize, //generation the genome was evaluated in
+}
+
+
+
+/// The best distinct genomes ever evaluated, sorted from best to worst
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HallOfFame {
+    pub capacity: usize,
+    entries: Vec<HallOfFameEntry>,
+}
+impl HallOfFame {
+    pub fn new(capacity: usize) -> Self {
+        HallOfFame {
+            capacity,
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+
+    /// Check if a fitness is good enough to enter, avoids cloning genomes that would be rejected anyway
+    /// Non-finite fitness never qualifies, JSON can't hold it
+    pub fn qualifies(&self, fitness: f64) -> bool {
+        if self.capacity == 0 || !fitness.is_finite() {
+            return false;
+        }
+        self.entries.len() < self.capacity || self.entries.last().is_some_and(|e| fitness > e.fitness)
+    }
+
+
+    /// Offer a genome to the hall of fame, returns true if it was entered
+    /// A genome already present only has its entry updated if it scored higher
+    pub fn offer(&mut self, genome: &Genome, fitness: f64, generation: usize) -> bool {
+        if !self.qualifies(fitness) {
+            return false;
+        }
+
+        // keep the genomes distinct
+        if let Some(i) = self.entries.iter().position(|e| e.genome == *genome) {
+            if self.entries[i].fitness >= fitness {
+                return false;
+            }
+            self.entries.remove(i);
+        }
+
+        let entry = HallOfFameEntry {
+            genome: genome.clone(),
+            fitness,
+            generation,
+        };
+        let i = self.entries.partition_point(|e| e.fitness >= fitness); //ties keep the older entry first
+        self.entries.insert(i, entry);
+        self.entries.truncate(self.capacity);
+        true
+    }
+
+
+    /// The best genome ever seen
+    pub fn champion(&self) -> Option<&HallOfFameEntry> {
+        self.entries.first()
+    }
+
+
+    /// All entries, best first
+    pub fn entries(&self) -> &[HallOfFameEntry] {
+        &self.entries
+    }
+
+
+    /// Write all entries to a JSON file
+    pub fn export<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, &self.entries)?;
+        writer.flush()
+    }
+}
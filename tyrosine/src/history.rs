@@ -0,0 +1,172 @@
+use std::{collections::BTreeMap, fmt::Write as _, fs::File, io::{self, BufWriter, Write}, path::Path};
+use serde::{Deserialize, Serialize};
+use crate::{genome::Genome, species::Species};
+
+
+
+/// One species as it was in one evaluated generation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpeciesSnapshot {
+    pub generation: usize,
+    pub species_id: usize,
+    pub size: usize,
+    pub mean_fitness: f64, //raw fitness, before sharing or penalties
+    pub best_fitness: f64,
+    pub type_specimen: Genome,
+}
+
+
+
+/// Where a species came from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeciesOrigin {
+    pub generation: usize, //first generation the species was part of
+    pub parent: Option<usize>, //species it split off from, None for the first species
+}
+
+
+
+/// Size, fitness and type specimen of every species in every generation, and which species split off from which
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SpeciationHistory {
+    snapshots: Vec<SpeciesSnapshot>, //in generation order
+    origins: BTreeMap<usize, SpeciesOrigin>, //species id to its origin
+}
+impl SpeciationHistory {
+    pub fn new() -> Self {
+        SpeciationHistory { snapshots: Vec::new(), origins: BTreeMap::new() }
+    }
+
+
+    /// Remember every species of an evaluated generation, their members need a fitness
+    /// Species without a recorded split are assumed to have no parent
+    pub fn record(&mut self, generation: usize, species: &[Species]) {
+        for spec in species {
+            let fitnesses = spec.members.iter().map(|p| p.fitness.expect("Recorded members have a fitness.")).collect::<Vec<_>>();
+            self.origins.entry(spec.id).or_insert(SpeciesOrigin { generation, parent: None });
+            self.snapshots.push(SpeciesSnapshot {
+                generation,
+                species_id: spec.id,
+                size: spec.members.len(),
+                mean_fitness: fitnesses.iter().sum::<f64>() / fitnesses.len().max(1) as f64,
+                best_fitness: fitnesses.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+                type_specimen: spec.type_specimen.clone(),
+            });
+        }
+    }
+
+
+    /// Remember that a new species split off from another one
+    pub fn record_split(&mut self, species_id: usize, parent: Option<usize>, generation: usize) {
+        self.origins.entry(species_id).or_insert(SpeciesOrigin { generation, parent });
+    }
+
+
+    /// Every snapshot in generation order
+    pub fn snapshots(&self) -> &[SpeciesSnapshot] {
+        &self.snapshots
+    }
+
+
+    /// Snapshots of one generation
+    pub fn generation(&self, generation: usize) -> impl Iterator<Item = &SpeciesSnapshot> {
+        self.snapshots.iter().filter(move |s| s.generation == generation)
+    }
+
+
+    /// Origin of a species, if it was ever recorded
+    pub fn origin(&self, species_id: usize) -> Option<SpeciesOrigin> {
+        self.origins.get(&species_id).copied()
+    }
+
+
+    /// Species that split off from the given one
+    pub fn children(&self, species_id: usize) -> Vec<usize> {
+        self.origins.iter()
+            .filter(|(_, o)| o.parent == Some(species_id))
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+
+    /// Write one CSV row per species per generation, the type specimen is summarized by its size
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "generation,species_id,parent_id,size,mean_fitness,best_fitness,specimen_nodes,specimen_connections")?;
+        for s in &self.snapshots {
+            let parent = self.origins.get(&s.species_id).and_then(|o| o.parent).map_or(String::new(), |p| p.to_string());
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{}",
+                s.generation, s.species_id, parent, s.size, s.mean_fitness, s.best_fitness,
+                s.type_specimen.node_genes.len(), s.type_specimen.connection_genes.iter().filter(|c| c.enabled).count(),
+            )?;
+        }
+        Ok(())
+    }
+
+
+    /// Write the history to a CSV file
+    pub fn export_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()
+    }
+
+
+    /// Write the whole history to a JSON file, type specimens included
+    pub fn export_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+
+
+    /// Draw a stacked-area chart as in the NEAT paper, generations go left to right
+    /// Every band is one species' share of the population, older species are stacked below newer ones
+    pub fn to_svg(&self, width: f64, height: f64) -> String {
+        let mut generations = self.snapshots.iter().map(|s| s.generation).collect::<Vec<_>>();
+        generations.dedup(); //snapshots are in generation order
+        let mut sizes: BTreeMap<usize, Vec<usize>> = BTreeMap::new(); //species id to its size in every generation
+        for s in &self.snapshots {
+            let column = generations.binary_search(&s.generation).unwrap(); //safe unwrap, taken from the snapshots
+            sizes.entry(s.species_id).or_insert_with(|| vec![0; generations.len()])[column] = s.size;
+        }
+        let totals = (0..generations.len())
+            .map(|g| sizes.values().map(|v| v[g]).sum::<usize>().max(1) as f64)
+            .collect::<Vec<_>>();
+        let x = |g: usize| g as f64 * width / (generations.len().max(2) - 1) as f64;
+
+        let mut svg = String::new();
+        writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", width, height, width, height).unwrap(); //writing to a String can't fail
+        let mut below = vec![0; generations.len()]; //members already stacked in every generation
+        for (id, sizes) in &sizes {
+            // a band spans from the species' first to last generation, and is flat wherever it's empty
+            let first = sizes.iter().position(|&s| s > 0).unwrap(); //safe unwrap, every species has a snapshot
+            let last = sizes.iter().rposition(|&s| s > 0).unwrap(); //safe unwrap
+            let y = |g: usize, count: usize| height - count as f64 / totals[g] * height;
+            let top = (first..=last).map(|g| format!("{:.2},{:.2}", x(g), y(g, below[g] + sizes[g])));
+            let bottom = (first..=last).rev().map(|g| format!("{:.2},{:.2}", x(g), y(g, below[g])));
+            let points = top.chain(bottom).collect::<Vec<_>>().join(" ");
+            let hue = (*id as f64 * 137.508) % 360.0; //golden angle keeps neighbouring species apart
+            writeln!(svg, "  <polygon points=\"{}\" fill=\"hsl({:.0}, 65%, 55%)\" stroke=\"white\" stroke-width=\"0.5\"><title>species {}</title></polygon>", points, hue, id).unwrap();
+            for (b, s) in below.iter_mut().zip(sizes) {
+                *b += s;
+            }
+        }
+        writeln!(svg, "</svg>").unwrap();
+        svg
+    }
+
+
+    /// Write the stacked-area chart to an SVG file
+    pub fn export_svg<P: AsRef<Path>>(&self, path: P, width: f64, height: f64) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(self.to_svg(width, height).as_bytes())?;
+        writer.flush()
+    }
+}
@@ -1,7 +1,19 @@
+pub mod config;
 pub mod genome;
+pub mod mutation;
 pub mod phenotype;
 pub mod species;
+pub mod speciation;
+pub mod hall_of_fame;
+pub mod history;
+pub mod lineage;
+pub mod map_elites;
+pub mod novelty;
+pub mod nsga;
 pub mod population;
+pub mod archipelago;
+pub mod render;
+pub mod reporter;
 
 
 
@@ -11,13 +23,12 @@ pub fn add(left: u64, right: u64) -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use crate::population::Population;
-
-    use super::*;
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+    use crate::{archipelago::{Archipelago, MigrationTopology}, config::{Config, InitialTopology, MutationRates, NoveltySearch, Parsimony, RateFactors, Schedule, SelfAdaptation, WeightInit}, genome::{Aggregation, Genome, GlobalInnovator}, hall_of_fame::HallOfFame, history::SpeciationHistory, lineage::Lineage, map_elites::{DescriptorAxis, MapElites}, mutation::{MutationContext, MutationOperator, MutationOperators}, novelty::{Behavior, NoveltyArchive}, nsga, phenotype::Phenotype, population::Population, reporter::CsvReporter, speciation::{CentroidFit, DistanceCache, FirstFit, KMedoids, Speciation, SpeciationStrategy}, species::{Species, SpeciesCounter}};
 
     #[test]
     fn generate_population() {
-        let pop = Population::new(100, 7, 1000);
+        let _pop = Population::new(100, 7, 1000);
     }
 
     #[test]
@@ -30,7 +41,7 @@ mod tests {
     fn population_evolve() {
         let mut pop = Population::new(100, 7, 10);
         let species_pre = pop.species.clone();
-        pop.evolve(&vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
+        pop.evolve(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
         let species_post = pop.species.clone();
         if species_pre.len() != species_post.len() {
             return; // something has changed, so evolution has taken place
@@ -45,17 +56,825 @@ mod tests {
                 }
             }
         }
-        assert!(false, "This test can be ignored; Population should be different after evolving.");
+        panic!("This test can be ignored; Population should be different after evolving.");
     }
 
     #[test]
     fn activate() {
         let pop = Population::new(5, 2, 5);
-        let res = pop.activate_index(0, &mut vec![2.0, 1.0, 1.5, 0.0, 4.0]);
+        let res = pop.activate_index(0, &mut [2.0, 1.0, 1.5, 0.0, 4.0]);
         println!("{:?}", res);
         //println!("{:?}", pop.species[0].members[0].);
         //panic!();
     }
-}
 
+    #[test]
+    fn render_phenotype() {
+        let pop = Population::new(3, 2, 5);
+        let phenotype = &pop.species[0].members[0];
+        let dot = phenotype.to_dot(true);
+        assert!(dot.starts_with("digraph"), "DOT output is a digraph.");
+        assert_eq!(dot.matches("fillcolor").count(), phenotype.genome.node_genes.len(), "Every node is written.");
+        let svg = phenotype.to_svg(true);
+        assert_eq!(svg.matches("<circle").count(), phenotype.genome.node_genes.len(), "Every node is drawn.");
+    }
+
+    #[test]
+    fn csv_reporter() {
+        let mut pop = Population::new(3, 2, 10);
+        let reporter = Rc::new(RefCell::new(CsvReporter::new(Vec::new())));
+        pop.add_reporter(reporter.clone());
+        pop.evolve(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
+        pop.evolve(&[1.0; 10]);
+        pop.end_run();
+        let csv = String::from_utf8(reporter.borrow().get_ref().clone()).unwrap();
+        assert_eq!(csv.lines().count(), 3, "Header and one row per generation.");
+        assert_eq!(pop.last_stats().unwrap().best_fitness, 1.0, "Last stats are from the final generation.");
+    }
+
+    #[test]
+    fn hall_of_fame() {
+        let mut pop = Population::new(3, 2, 10);
+        pop.evolve(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
+        pop.evolve(&[0.5; 10]);
+        let champion = pop.champion().unwrap();
+        assert_eq!(champion.fitness, 10.0, "Champion survives a worse generation.");
+        assert_eq!(champion.generation, 0, "Champion remembers its generation.");
+
+        let path = std::env::temp_dir().join("tyrosine_hall_of_fame_test.json");
+        pop.save(&path).unwrap();
+        let loaded = Population::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.hall_of_fame(), pop.hall_of_fame(), "Hall of fame is saved with the population.");
+
+        // well-formed files with members that don't add up are turned away instead of panicking
+        let mut broken = serde_json::to_value(&pop).unwrap();
+        broken["species"][0]["members"] = serde_json::json!([]);
+        std::fs::write(&path, broken.to_string()).unwrap();
+        let error = Population::load(&path).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "Empty species are invalid data.");
+        let mut broken = serde_json::to_value(&pop).unwrap();
+        broken["population_size"] = serde_json::json!(11);
+        std::fs::write(&path, broken.to_string()).unwrap();
+        let error = Population::load(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "Mismatched sizes are invalid data.");
+
+        let mut hall = HallOfFame::new(2);
+        assert!(!hall.offer(&champion.genome, f64::INFINITY, 0), "Infinite fitness can't be saved.");
+        assert!(!hall.offer(&champion.genome, f64::NAN, 0), "NaN fitness can't be compared.");
+        assert!(hall.entries().is_empty(), "Nothing non-finite is entered.");
+    }
+
+    #[test]
+    fn fitness_sharing() {
+        let speciation = SpeciationStrategy::new(FirstFit { threshold: 0.5 }); //several species of different sizes
+        let config = Config { fitness_sharing: true, speciation, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 30, config);
+        let mut fitnesses = vec![];
+        let mut species_of = HashMap::new(); //member id to its species index and size
+        for (s_i, spec) in pop.species.iter().enumerate() {
+            for (m_i, phen) in spec.members.iter().enumerate() {
+                fitnesses.push(((s_i + 1) * 3) as f64 + (m_i % 3) as f64 * 0.5);
+                species_of.insert(phen.id.unwrap(), (s_i, spec.members.len()));
+            }
+        }
+        pop.evolve(&fitnesses);
+
+        // every species gets the sum of its members' shared fitness, which is their mean
+        let stats = pop.last_stats().unwrap().clone();
+        let mut offset = 0;
+        for (&size, &shared) in stats.species_sizes.iter().zip(&stats.species_fitnesses) {
+            let mean = fitnesses[offset..offset + size].iter().sum::<f64>() / size as f64;
+            assert!((shared - mean).abs() < 1e-9, "Shared species fitness is the mean fitness.");
+            offset += size;
+        }
+
+        // survivors keep the adjusted fitness they were selected with, children are counted against their parents' species
+        let mut slots = vec![0; stats.species_count];
+        for phen in pop.species.iter().flat_map(|s| &s.members) {
+            let id = phen.id.unwrap();
+            match species_of.get(&id) {
+                Some(&(s_i, size)) => {
+                    assert_eq!(phen.adjusted_fitness, Some(phen.fitness.unwrap() / size as f64), "Adjusted fitness is divided by the species size.");
+                    slots[s_i] += 1;
+                },
+                None => slots[species_of[&phen.parents[0]].0] += 1,
+            }
+        }
+        let total = stats.species_fitnesses.iter().sum::<f64>();
+        let floors = stats.species_fitnesses.iter().map(|f| (f / total * 30.0) as usize).collect::<Vec<_>>();
+        let extra = (30 - floors.iter().sum::<usize>()).div_ceil(stats.species_count);
+        for (&got, &floor) in slots.iter().zip(&floors) {
+            assert!(got >= floor && got <= floor + extra, "Slots follow shared species fitness.");
+        }
+    }
+
+    #[test]
+    fn species_age() {
+        let config = Config { young_fitness_boost: 2.0, old_fitness_penalty: 0.5, protected_species_age: 100, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 20, config.clone());
+        for _ in 0..3 {
+            let fitnesses: Vec<f64> = (0..20).map(|x| x as f64).collect();
+            pop.evolve(&fitnesses);
+        }
+        let stats = pop.last_stats().unwrap();
+        assert!(stats.species_ages.iter().all(|&age| age <= 2), "Species are at most as old as the run.");
+
+        let mut spec = pop.species[0].clone();
+        spec.age = 0;
+        assert_eq!(spec.age_multiplier(&config), 2.0, "Young species are boosted.");
+        assert!(spec.is_protected(&config), "Young species are protected.");
+        spec.age = config.old_species_age;
+        assert_eq!(spec.age_multiplier(&config), 0.5, "Old species are penalized.");
+
+        // a brand new species with the worst fitness still breeds its protected slots
+        let speciation = SpeciationStrategy::new(FirstFit { threshold: f64::INFINITY }); //everyone else in one old species
+        let config = Config { protected_species_age: 3, speciation, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 20, config.clone());
+        pop.species[0].age = 5;
+        let newcomer = pop.species[0].members.pop().unwrap();
+        let newcomer_id = newcomer.id.unwrap();
+        let mut young = Species::new(&newcomer.genome, 1000);
+        young.members.push(newcomer);
+        pop.species.push(young);
+        pop.update_cache();
+        let fitnesses = (0..20).map(|i| if i == 19 { 0.0 } else { 10.0 }).collect::<Vec<_>>();
+        pop.evolve(&fitnesses);
+        let descendants = pop.species.iter()
+            .flat_map(|s| &s.members)
+            .filter(|p| p.id == Some(newcomer_id) || p.parents.first() == Some(&newcomer_id))
+            .count();
+        assert_eq!(descendants, config.protected_species_slots, "Protected species keep their minimum slots.");
+    }
+
+    #[test]
+    fn deletion_mutations() {
+        let grow = Config { mutation: MutationRates { add_connection: 1.0, add_node: 1.0, ..MutationRates::default() }, ..Config::default() };
+        let shrink = Config { mutation: MutationRates { add_connection: 0.0, add_node: 0.0, remove_connection: 1.0, remove_node: 1.0, ..MutationRates::default() }, ..Config::default() };
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let mut genome = Genome::new(3, 2);
+        for _ in 0..20 {
+            genome.mutate(&grow, &mut innovator, &mut innovations);
+        }
+        let grown = genome.complexity();
+        assert!(grown > 0, "Addition mutations grow the genome.");
+        for _ in 0..100 {
+            genome.mutate(&shrink, &mut innovator, &mut innovations);
+        }
+        assert!(genome.connection_genes.is_empty(), "Every connection was removed.");
+        assert_eq!(genome.node_genes.len(), 6, "Inputs, bias and outputs are never removed.");
+    }
+
+    #[test]
+    fn pruning() {
+        let grow = Config { mutation: MutationRates { add_connection: 0.8, add_node: 0.5, toggle: 0.1, ..MutationRates::default() }, ..Config::default() };
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let mut phenotype = Phenotype::from_genome(Genome::new(3, 2)).unwrap();
+        for _ in 0..30 {
+            phenotype = Phenotype::from_mutation(&phenotype.genome, &grow, &mut innovator, &mut innovations);
+        }
+
+        let (pruned, report) = phenotype.genome.pruned();
+        assert_eq!(pruned.connection_genes.len() + report.removed_connections.len(), phenotype.genome.connection_genes.len(), "Every connection is kept or reported.");
+        assert_eq!(pruned.node_genes.len() + report.removed_nodes.len(), phenotype.genome.node_genes.len(), "Every node is kept or reported.");
+
+        let pruned = Phenotype::from_genome(pruned).unwrap();
+        let compiled = Phenotype::from_genome_with(phenotype.genome.clone(), true).unwrap();
+        let svg = compiled.to_svg(true);
+        assert_eq!(svg.matches("<circle").count(), compiled.genome.node_genes.len(), "Nodes left out of compilation are still drawn.");
+        let inputs = [0.3, -1.2, 2.0];
+        assert_eq!(pruned.activate(&inputs), phenotype.activate(&inputs), "Pruned genome is functionally equivalent.");
+        assert_eq!(compiled.activate(&inputs), phenotype.activate(&inputs), "Compiling only output ancestors is functionally equivalent.");
+    }
+
+    #[test]
+    fn node_bias() {
+        let config = Config { node_bias: true, mutation: MutationRates { add_connection: 0.0, add_node: 0.0, bias: 1.0, ..MutationRates::default() }, ..Config::default() };
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let mut genome = Genome::new(2, 1);
+        genome.mutate(&config, &mut innovator, &mut innovations);
+        assert!(genome.node_genes[..3].iter().all(|n| n.bias == 0.0), "Inputs never get a bias.");
+        assert_ne!(genome.node_genes[3].bias, 0.0, "Outputs get a bias.");
+
+        let phenotype = Phenotype::from_genome(genome.clone()).unwrap();
+        assert_eq!(phenotype.activate(&[1.0, 1.0]), vec![genome.node_genes[3].bias.tanh()], "An unconnected output only sees its bias.");
+    }
+
+    #[test]
+    fn aggregation() {
+        let mut values = [3.0, -1.0, 2.0, 0.5];
+        assert_eq!(Aggregation::Sum.aggregate(&mut values), 4.5);
+        assert_eq!(Aggregation::Product.aggregate(&mut values), -3.0);
+        assert_eq!(Aggregation::Max.aggregate(&mut values), 3.0);
+        assert_eq!(Aggregation::Min.aggregate(&mut values), -1.0);
+        assert_eq!(Aggregation::Mean.aggregate(&mut values), 1.125);
+        assert_eq!(Aggregation::Median.aggregate(&mut values), 1.25);
+        assert_eq!(Aggregation::Product.aggregate(&mut []), 0.0, "Nodes without inputs aggregate to 0.");
+    }
+
+    #[test]
+    fn weight_clamp_and_init() {
+        let config = Config {
+            weight_init: WeightInit::Uniform { min: -0.5, max: 0.5 },
+            weight_clamp: Some((-1.0, 1.0)),
+            mutation: MutationRates { add_connection: 1.0, add_node: 0.0, perturbation_std: 5.0, replacement_range: 50.0, ..MutationRates::default() },
+            ..Config::default()
+        };
+        let pop = Population::with_config(3, 2, 20, config.clone());
+        let connections = pop.species.iter().flat_map(|s| &s.members).flat_map(|p| &p.genome.connection_genes);
+        assert!(connections.clone().count() > 0, "Initial mutations add connections.");
+        assert!(connections.clone().all(|c| c.weight.abs() <= 0.5), "Initial connections use the configured distribution.");
+
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let mut genome = Genome::new(3, 2);
+        for _ in 0..50 {
+            genome.mutate(&config, &mut innovator, &mut innovations);
+        }
+        assert!(genome.connection_genes.iter().all(|c| c.weight.abs() <= 1.0), "Weights stay within the clamp.");
+
+        let inverted = Config { weight_clamp: Some((1.0, -1.0)), ..Config::default() };
+        assert_eq!(inverted.clamp_weight(5.0), 1.0, "Inverted bounds are swapped.");
+        let nan = Config { weight_clamp: Some((f64::NAN, 0.5)), ..Config::default() };
+        assert_eq!(nan.clamp_weight(2.0), 0.5, "NaN bounds are ignored.");
+        assert_eq!(nan.clamp_weight(-2.0), -2.0, "NaN bounds are ignored.");
+    }
+
+    #[test]
+    fn initial_topology() {
+        let connections = |topology| {
+            let config = Config { initial_topology: topology, ..Config::default() };
+            let pop = Population::with_config(3, 2, 10, config);
+            pop.species.iter()
+                .flat_map(|s| &s.members)
+                .map(|p| p.genome.connection_genes.iter().map(|c| c.innov).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        for genome in connections(InitialTopology::FullyConnected) {
+            assert_eq!(genome, (0..8).collect::<Vec<_>>(), "Every input and the bias reach every output.");
+        }
+        for genome in connections(InitialTopology::BiasOnly) {
+            assert_eq!(genome.len(), 2, "Only the bias is connected.");
+        }
+        for genome in connections(InitialTopology::FullyConnectedHidden(2)) {
+            assert_eq!(genome.len(), 4 * 2 + 2 * 2, "Inputs reach hidden nodes which reach outputs.");
+        }
+        for genome in connections(InitialTopology::Partial(0.0)) {
+            assert!(genome.is_empty(), "No connections with a probability of 0.");
+        }
+
+        // the default constant initialisation still gives every genome its own weights
+        let pop = Population::with_config(3, 2, 10, Config { initial_topology: InitialTopology::FullyConnected, ..Config::default() });
+        let weights = pop.species.iter()
+            .flat_map(|s| &s.members)
+            .map(|p| p.genome.connection_genes.iter().map(|c| c.weight.to_bits()).collect::<Vec<_>>())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(weights.len(), 10, "Wired genomes start with different weights.");
+    }
+
+    struct FlattenWeights;
+    impl MutationOperator for FlattenWeights {
+        fn name(&self) -> &str {
+            "flatten"
+        }
+
+        fn mutate(&self, genome: &mut Genome, _context: &mut MutationContext) {
+            for conn in &mut genome.connection_genes {
+                conn.weight = 0.25;
+            }
+        }
+    }
+
+    struct ReverseGenes;
+    impl MutationOperator for ReverseGenes {
+        fn name(&self) -> &str {
+            "reverse"
+        }
+
+        fn mutate(&self, genome: &mut Genome, _context: &mut MutationContext) {
+            genome.connection_genes.reverse();
+        }
+    }
+
+    #[test]
+    fn custom_mutation_operator() {
+        let mut config = Config { initial_topology: InitialTopology::FullyConnected, ..Config::default() };
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let mut genome = Genome::with_topology(3, 2, config.initial_topology, &config, &mut innovator, &mut innovations);
+
+        config.mutation_operators.register(1.0, FlattenWeights);
+        assert!(config.mutation_operators.unregister("weight"), "Built-in operators can be removed.");
+        genome.mutate(&config, &mut innovator, &mut innovations);
+        assert!(genome.connection_genes.iter().all(|c| c.weight == 0.25), "Custom operators run after the built-ins.");
+
+        config.mutation_operators = MutationOperators::empty();
+        config.mutation_operators.register(0.0, FlattenWeights);
+        genome.connection_genes[0].weight = 2.0;
+        genome.mutate(&config, &mut innovator, &mut innovations);
+        assert_eq!(genome.connection_genes[0].weight, 2.0, "Operators with no weight never run.");
+
+        config.mutation_operators.register(1.0, ReverseGenes);
+        genome.mutate(&config, &mut innovator, &mut innovations);
+        assert!(genome.connection_genes.is_sorted_by_key(|c| c.innov), "Genes are sorted again after custom operators.");
+    }
+
+    #[test]
+    fn saved_mutation_operators() {
+        let mut config = Config::default();
+        assert!(config.mutation_operators.unregister("toggle"), "Built-in operators can be removed.");
+        assert!(config.mutation_operators.set_weight("add_node", 0.5), "Built-in operators can be reweighted.");
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#"{"name":"add_node","weight":0.5}"#), "Built-in operators are saved with their weights.");
+        let loaded = serde_json::from_str::<Config>(&json).unwrap();
+        assert_eq!(loaded.mutation_operators, config.mutation_operators, "Removed and reweighted operators stay that way.");
+
+        let unknown = json.replace(r#""name":"bias""#, r#""name":"flatten""#);
+        assert!(serde_json::from_str::<Config>(&unknown).is_err(), "Unknown operators fail to load.");
+        config.mutation_operators.register(1.0, FlattenWeights);
+        assert!(serde_json::to_string(&config).is_err(), "Custom operators fail to save.");
+    }
+
+    #[test]
+    fn self_adaptive_rates() {
+        let config = Config { self_adaptation: Some(SelfAdaptation::default()), ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 10, config);
+        pop.evolve(&[1.0; 10]);
+        pop.evolve(&[1.0; 10]);
+        let members = pop.species.iter().flat_map(|s| &s.members).collect::<Vec<_>>();
+        assert!(members.iter().all(|p| p.genome.rate_factors.is_some()), "Mutated genomes carry their own factors.");
+        assert!(members.iter().any(|p| p.genome.rate_factors.unwrap() != RateFactors::default()), "Factors drift from 1.");
+        assert!(pop.last_stats().unwrap().mean_mutation_rates.is_some(), "Mean rates appear in the stats.");
+
+        let adaptation = SelfAdaptation::default();
+        let factors = RateFactors { add_connection: 0.0001, add_node: 100.0, ..RateFactors::default() };
+        let rates = factors.apply(&MutationRates::default(), &adaptation);
+        assert_eq!(rates.remove_node, 0.0, "Disabled rates stay disabled.");
+        assert_eq!(rates.add_connection, adaptation.min_rate, "Enabled rates don't drop under the floor.");
+        assert_eq!(rates.add_node, 1.0, "Rates stay probabilities.");
+        let simplifying = MutationRates { add_connection: 0.0, remove_node: 0.2, ..MutationRates::default() };
+        let rates = factors.apply(&simplifying, &adaptation);
+        assert_eq!(rates.add_connection, 0.0, "Rates disabled this generation stay disabled.");
+        assert!(rates.remove_node > 0.0, "Rates enabled this generation get used.");
+
+        let mut rng = rand::rng();
+        for _ in 0..100 {
+            let mut next = RateFactors::default();
+            next.adapt(&adaptation, &mut rng);
+            assert!(next.add_connection <= adaptation.max_factor && next.add_connection >= 1.0 / adaptation.max_factor, "Adapted factors stay in range.");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Self-adaptation min rate is between 0 and 1.")]
+    fn invalid_self_adaptation() {
+        let config = Config { self_adaptation: Some(SelfAdaptation { min_rate: f64::NAN, ..SelfAdaptation::default() }), ..Config::default() };
+        Population::with_config(3, 2, 10, config);
+    }
+
+    #[test]
+    fn annealing_schedules() {
+        assert_eq!(Schedule::Constant.multiplier(100, 100), 1.0, "Constant schedules never change.");
+        let linear = Schedule::Linear { start: 2.0, end: 0.5, generations: 10 };
+        assert_eq!(linear.multiplier(0, 0), 2.0, "Linear schedules begin at the start.");
+        assert_eq!(linear.multiplier(5, 0), 1.25, "Linear schedules move halfway by the middle.");
+        assert_eq!(linear.multiplier(50, 0), 0.5, "Linear schedules stay at the end.");
+        let exponential = Schedule::Exponential { start: 1.0, decay: 0.5, min: 0.1 };
+        assert_eq!(exponential.multiplier(2, 0), 0.25, "Exponential schedules decay every generation.");
+        assert_eq!(exponential.multiplier(20, 0), 0.1, "Exponential schedules respect the floor.");
+        let boost = Schedule::StagnationBoost { stall_generations: 3, boost: 4.0 };
+        assert_eq!(boost.multiplier(10, 2), 1.0, "No boost before stagnating.");
+        assert_eq!(boost.multiplier(10, 3), 4.0, "Boosted once stagnating.");
+
+        let config = Config { mutation_power_schedule: boost, structural_schedule: exponential, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 10, config);
+        for _ in 0..4 {
+            pop.evolve(&[1.0; 10]);
+        }
+        assert_eq!(pop.stagnant_generations(), 3, "Stagnation counts generations without a new best.");
+
+        let overshoot = Schedule::Linear { start: 1.0, end: -1.0, generations: 2 };
+        let config = Config { mutation_power_schedule: overshoot, structural_schedule: overshoot, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 10, config);
+        for _ in 0..5 {
+            pop.evolve(&[1.0; 10]); //negative multipliers past the end mustn't give a negative std
+        }
+        assert_eq!(pop.generation_config().mutation.perturbation_std, 0.0, "Scheduled stds stop at 0.");
+    }
+
+    #[test]
+    fn speciation_strategies() {
+        let config = Config {
+            initial_topology: InitialTopology::FullyConnected,
+            weight_init: WeightInit::Uniform { min: -2.0, max: 2.0 },
+            speciation: SpeciationStrategy::new(KMedoids { species_count: 3, ..KMedoids::default() }),
+            ..Config::default()
+        };
+        let mut pop = Population::with_config(3, 2, 20, config);
+        assert_eq!(pop.species.len(), 3, "K-medoids keeps a fixed number of species.");
+        pop.evolve(&[1.0; 20]);
+        assert!(pop.species.len() <= 3, "K-medoids never grows past its species count.");
+
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let config = Config { initial_topology: InitialTopology::FullyConnected, ..Config::default() };
+        let mut genome = Genome::with_topology(3, 2, config.initial_topology, &config, &mut innovator, &mut innovations);
+        let mut other = genome.clone();
+        for (conn, other_conn) in genome.connection_genes.iter_mut().zip(&mut other.connection_genes) {
+            conn.weight = 1.0;
+            other_conn.weight = 3.0;
+        }
+        let mut species = Species::new(&genome, 0);
+        species.members = vec![Phenotype::from_genome(genome).unwrap(), Phenotype::from_genome(other).unwrap()];
+        CentroidFit::default().choose_representative(&mut species, &config);
+        assert!(species.type_specimen.connection_genes.iter().all(|c| c.weight == 2.0), "Centroid weights are averaged.");
+
+        let config = Config { speciation: SpeciationStrategy::new(CentroidFit::default()), ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 10, config);
+        pop.evolve(&[1.0; 10]);
+        assert_eq!(pop.species.iter().map(|s| s.members.len()).sum::<usize>(), 10, "Centroid speciation places everyone.");
+    }
+
+    struct SingleSpecies;
+    impl Speciation for SingleSpecies {
+        fn name(&self) -> &str {
+            "single"
+        }
+
+        fn speciate(&self, species: &mut Vec<Species>, phenotypes: Vec<Phenotype>, species_counter: &mut SpeciesCounter, config: &Config) {
+            FirstFit { threshold: f64::INFINITY }.speciate(species, phenotypes, species_counter, config);
+        }
+    }
+
+    #[test]
+    fn saved_speciation() {
+        let k_medoids = KMedoids { species_count: 3, max_iterations: 4, cache_distances: false };
+        let config = Config { speciation: SpeciationStrategy::new(k_medoids), ..Config::default() };
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""speciation":{"name":"k_medoids","species_count":3,"max_iterations":4,"cache_distances":false}"#), "Built-in strategies are saved with their parameters.");
+        let loaded = serde_json::from_str::<Config>(&json).unwrap();
+        assert_eq!(loaded.speciation, config.speciation, "Built-in strategies are loaded again.");
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json, "Parameters survive loading.");
+
+        let unknown = json.replace("k_medoids", "single");
+        assert!(serde_json::from_str::<Config>(&unknown).is_err(), "Unknown strategies fail to load.");
+        let config = Config { speciation: SpeciationStrategy::new(SingleSpecies), ..Config::default() };
+        assert!(serde_json::to_string(&config).is_err(), "Custom strategies fail to save.");
+    }
+
+    #[test]
+    fn distance_cache() {
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let config = Config { initial_topology: InitialTopology::FullyConnected, ..Config::default() };
+        let genome = Genome::with_topology(3, 2, config.initial_topology, &config, &mut innovator, &mut innovations);
+        let mut other = genome.clone();
+        other.connection_genes.remove(2);
+        other.add_node(&config, &mut innovator, &mut innovations);
+        let distance = Species::compatibility_distance(&genome, &other);
+        assert_eq!(distance, Species::compatibility_distance(&other, &genome), "Distance is symmetric.");
+
+        let mut cache = DistanceCache::new(true, 1);
+        assert_eq!(cache.distance(0, 0, || distance), distance, "Cache computes unknown distances.");
+        assert_eq!(cache.distance(0, 0, || 0.0), distance, "Cache remembers known distances.");
+        cache.forget(0);
+        assert_eq!(cache.distance(0, 0, || 0.0), 0.0, "Forgotten distances are computed again.");
+    }
+
+    #[test]
+    fn compatibility_distance() {
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let config = Config { initial_topology: InitialTopology::FullyConnected, ..Config::default() };
+        let genome = Genome::with_topology(3, 2, config.initial_topology, &config, &mut innovator, &mut innovations);
+        let empty = Genome::new(3, 2);
+        assert_eq!(Species::compatibility_distance(&genome, &genome.clone()), 0.0, "Identical genomes have no distance.");
+        assert_eq!(Species::compatibility_distance(&empty, &Genome::new(3, 2)), 0.0, "Empty genomes share a species.");
+        assert_eq!(Species::compatibility_distance(&genome, &empty), 1.0, "Every gene is excess against an empty genome.");
+
+        // the first half against the second half, with the larger genome as the normalizer
+        let mut first = genome.clone();
+        first.connection_genes.truncate(4);
+        let mut second = genome.clone();
+        second.connection_genes.drain(..4);
+        second.connection_genes.truncate(2);
+        assert_eq!(Species::compatibility_distance(&first, &second), 1.5, "Disjoint and excess genes are divided by the larger genome.");
+        assert_eq!(Species::compatibility_distance_with(&first, &second, 20), 6.0, "Small genomes aren't normalized.");
+
+        // under the defaults, genomes with no genes in common land in different species
+        let config = Config { initial_topology: InitialTopology::FullyConnected, ..Config::default() };
+        let disjoint = Genome::with_topology(3, 2, config.initial_topology, &config, &mut innovator, &mut HashMap::new()); //new innovation numbers
+        let phenotypes = [genome, disjoint, empty].map(|g| Phenotype::from_genome(g).unwrap());
+        let mut species = vec![];
+        config.speciation.speciate(&mut species, phenotypes.to_vec(), &mut SpeciesCounter::new(), &config);
+        assert_eq!(species.len(), 3, "Topology alone splits species.");
+    }
+
+    #[test]
+    fn novelty_search() {
+        let behaviors = [vec![0.0], vec![1.0], vec![3.0]].map(Behavior::from);
+        assert_eq!(NoveltyArchive::new(0).sparseness(&behaviors, 1), vec![1.0, 1.0, 2.0], "Sparseness is the distance to the nearest neighbours.");
+
+        let novelty = NoveltySearch { k: 3, archive_threshold: 0.0, archive_capacity: 15, ..NoveltySearch::default() };
+        let config = Config { novelty: Some(novelty), ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 10, config);
+        for _ in 0..2 {
+            let behaviors = (0..10)
+                .map(|i| Behavior(pop.activate_index(i, &mut [1.0, 0.5, -0.5])))
+                .collect::<Vec<_>>();
+            pop.evolve_with_behaviors(&[1.0; 10], &behaviors);
+        }
+        assert!(pop.novelty_archive().len() <= 15, "The archive respects its capacity.");
+        assert_eq!(pop.champion().unwrap().fitness, 1.0, "Raw fitness is still tracked.");
+
+        let path = std::env::temp_dir().join("tyrosine_novelty_test.json");
+        pop.save(&path).unwrap();
+        let loaded = Population::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.novelty_archive(), pop.novelty_archive(), "The archive is saved with the population.");
+    }
+
+    #[test]
+    fn map_elites() {
+        let axes = vec![DescriptorAxis::new(-1.0, 1.0, 4), DescriptorAxis::new(-1.0, 1.0, 4)];
+        let mut archive = MapElites::new(3, 2, axes, Config::default());
+        assert_eq!(archive.cell_index(&[-5.0, 0.9]), Some(3), "Out of range descriptors land in the edge bins.");
+        assert_eq!(archive.cell_index(&[f64::NAN, 0.0]), None, "NaN descriptors have no cell.");
+
+        for _ in 0..3 {
+            for phenotype in archive.offspring(20) {
+                let outputs = phenotype.activate(&[1.0, 0.5, -0.5]);
+                let fitness = 2.0 - outputs[0].abs();
+                archive.insert(&phenotype.genome, fitness, &outputs);
+            }
+        }
+        assert!(archive.coverage() > 0.0 && archive.coverage() <= 1.0, "Some cells are filled.");
+        let elite = archive.elites().next().unwrap().clone();
+        assert!(!archive.insert(&elite.genome, elite.fitness, &elite.descriptor), "Ties keep the older elite.");
+        assert!(archive.insert(&elite.genome, elite.fitness + 1.0, &elite.descriptor), "Fitter genomes replace the elite.");
+        assert!(!archive.insert(&elite.genome, f64::INFINITY, &elite.descriptor), "Infinite fitness is turned away.");
+        assert!(!archive.insert(&elite.genome, f64::NAN, &elite.descriptor), "NaN fitness is turned away.");
+        assert!(!archive.insert(&elite.genome, 100.0, &[f64::NEG_INFINITY, 0.0]), "Infinite descriptors are turned away.");
+        let qd_score = archive.elites().map(|e| e.fitness).sum::<f64>();
+        assert_eq!(archive.qd_score(), qd_score, "QD-score sums the elites' fitness.");
+
+        let path = std::env::temp_dir().join("tyrosine_map_elites_test.json");
+        archive.save(&path).unwrap();
+        let mut loaded = MapElites::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.elites().collect::<Vec<_>>(), archive.elites().collect::<Vec<_>>(), "Elites are saved with the archive.");
+        assert_eq!(loaded.offspring(5).len(), 5, "Loaded archives keep breeding.");
+    }
+
+    #[test]
+    fn multi_objective() {
+        let objectives = vec![vec![1.0, 1.0], vec![2.0, 0.0], vec![0.0, 2.0], vec![0.0, 0.0]];
+        assert_eq!(nsga::pareto_fronts(&objectives), vec![0, 0, 0, 1], "Only the last member is dominated.");
+        let fronts = nsga::pareto_fronts(&objectives);
+        assert_eq!(nsga::crowding_distances(&objectives, &fronts)[..3], [2.0, f64::INFINITY, f64::INFINITY], "Edges of a front are infinitely far.");
+        assert_eq!(nsga::ranks(&objectives)[3], 3, "Dominated members rank last.");
+
+        let config = Config { complexity_objective: true, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 10, config);
+        for _ in 0..3 {
+            let objectives = (0..10).map(|i| vec![i as f64, (10 - i) as f64]).collect::<Vec<_>>();
+            pop.evolve_multi(&objectives);
+        }
+        assert_eq!(pop.species.iter().map(|s| s.members.len()).sum::<usize>(), 10, "Population size is kept.");
+        assert_eq!(pop.champion().unwrap().fitness, 9.0, "The first objective is the fitness.");
+    }
+
+    #[test]
+    fn parsimony() {
+        let mut innovator = GlobalInnovator::new();
+        let mut innovations = HashMap::new();
+        let config = Config { initial_topology: InitialTopology::FullyConnectedHidden(2), ..Config::default() };
+        let genome = Genome::with_topology(3, 2, config.initial_topology, &config, &mut innovator, &mut innovations);
+        let linear = Parsimony { connection_penalty: 0.5, node_penalty: 1.0, ..Parsimony::default() };
+        assert_eq!(linear.penalty(&genome), 12.0 * 0.5 + 2.0, "Every connection and hidden node is penalized.");
+        let thresholded = Parsimony { free_connections: 10, free_nodes: 2, ..linear };
+        assert_eq!(thresholded.penalty(&genome), 1.0, "Only connections past the threshold are penalized.");
+
+        let config = Config { parsimony: Some(linear), initial_topology: InitialTopology::FullyConnected, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 10, config);
+        pop.evolve(&[1.0; 10]);
+        let stats = pop.last_stats().unwrap();
+        assert_eq!(stats.best_fitness, 1.0, "Raw fitness is reported.");
+        assert!(stats.best_penalized_fitness.unwrap() < 1.0, "Penalized fitness is reported.");
+        assert!(stats.mean_penalized_fitness.unwrap() <= stats.best_penalized_fitness.unwrap(), "Mean never beats the best.");
+
+        // species fitness of both signs mustn't hand out more slots than there are members
+        let speciation = SpeciationStrategy::new(FirstFit { threshold: 0.0 }); //every member in its own species
+        let config = Config { parsimony: Some(linear), speciation, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 10, config);
+        let fitnesses = (0..10).map(|i| if i % 2 == 0 { 10.0 } else { -9.0 }).collect::<Vec<_>>();
+        pop.evolve(&fitnesses);
+        pop.evolve(&[-1.0; 10]);
+        assert_eq!(pop.ids().len(), 10, "Mixed-sign species keep the population size.");
+    }
+
+    #[test]
+    fn steady_state() {
+        let mut pop = Population::new(3, 2, 10);
+        let ids = pop.ids();
+        assert_eq!(ids.iter().collect::<std::collections::HashSet<_>>().len(), 10, "Every member has its own id.");
+        assert_eq!(pop.replace_worst(0), None, "Nobody is eligible without a fitness.");
+        for (i, &id) in ids.iter().enumerate() {
+            assert!(pop.set_fitness(id, i as f64), "Members can be given a fitness.");
+        }
+        assert_eq!(pop.replace_worst(100), None, "Nobody is old enough.");
+
+        let replacement = pop.replace_worst(0).unwrap();
+        assert!(pop.get(replacement.removed).is_none(), "The worst member is removed.");
+        assert_eq!(pop.get(replacement.added).unwrap().age, 0, "The child is added.");
+        assert_eq!(pop.ids().len(), 10, "Population size is kept.");
+        assert!(!pop.set_fitness(replacement.removed, 1.0), "Removed members can't be given a fitness.");
+        for _ in 0..20 {
+            pop.replace_worst(0);
+        }
+        pop.evolve(&[1.0; 10]); //generational evolution still works afterwards
+
+        // saves from before ids existed get them when loading
+        let mut old_save = serde_json::to_value(&pop).unwrap();
+        for field in ["next_individual", "lineage", "species_history", "novelty_archive", "stagnant_generations", "search_phase"] {
+            old_save.as_object_mut().unwrap().remove(field);
+        }
+        for member in old_save["species"].as_array_mut().unwrap().iter_mut().flat_map(|s| s["members"].as_array_mut().unwrap()) {
+            member.as_object_mut().unwrap().remove("id");
+        }
+        let mut loaded = serde_json::from_value::<Population>(old_save).unwrap();
+        loaded.restore().unwrap();
+        assert_eq!(loaded.ids().iter().collect::<std::collections::HashSet<_>>().len(), 10, "Old saves are given ids.");
+
+        // parent species are picked in proportion to their average fitness, shifted only when some are negative
+        assert_eq!(Population::proportional_shares(&[10.0, 11.0]), vec![10.0, 11.0], "Positive averages keep their proportion.");
+        assert_eq!(Population::proportional_shares(&[-1.0, 1.0]), vec![0.0, 2.0], "Negative averages are shifted up to 0.");
+        assert_eq!(Population::proportional_shares(&[f64::NAN, 2.0]), vec![0.0, 2.0], "Non-finite averages get no share.");
+
+        // so two species averaging 10 and 11 parent children about 10 times in 21 and 11 times in 21
+        let speciation = SpeciationStrategy::new(FirstFit { threshold: f64::MAX }); //one species to split by hand, infinity can't be saved
+        let mut pop = Population::with_config(3, 2, 4, Config { speciation, ..Config::default() });
+        let moved = pop.species[0].members.split_off(2);
+        let mut second = Species::new(&moved[0].genome, 1000);
+        second.members = moved;
+        pop.species.push(second);
+        pop.update_cache();
+        let first_ids = pop.species[0].members.iter().map(|p| p.id.unwrap()).collect::<Vec<_>>();
+        for (i, id) in pop.ids().into_iter().enumerate() {
+            pop.set_fitness(id, if i < 2 { 10.0 } else { 11.0 });
+        }
+        let saved = serde_json::to_value(&pop).unwrap();
+        let trials = 300;
+        let from_first = (0..trials)
+            .filter(|_| {
+                let mut pop = serde_json::from_value::<Population>(saved.clone()).unwrap();
+                pop.restore().unwrap();
+                let added = pop.replace_worst(0).unwrap().added;
+                first_ids.contains(&pop.get(added).unwrap().parents[0])
+            })
+            .count();
+        let fraction = from_first as f64 / trials as f64; //10 / 21 expected
+        assert!(fraction > 0.3 && fraction < 0.65, "Parent species are picked in proportion to their average fitness.");
+    }
+
+    #[test]
+    fn lineage() {
+        let config = Config { track_lineage: true, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 20, config);
+        assert_eq!(pop.lineage().len(), 20, "The first generation is recorded.");
+        for _ in 0..3 {
+            let fitnesses = (0..20).map(|i| i as f64).collect::<Vec<_>>();
+            pop.evolve(&fitnesses);
+        }
+
+        // children of the last generation lead all the way back to the first one
+        let ids = pop.ids();
+        let child = ids.iter().map(|&id| pop.get(id).unwrap()).find(|p| p.birth_generation == 3).expect("Someone was born last generation.");
+        let id = child.id.unwrap();
+        assert!(!child.parents.is_empty(), "Children have parents.");
+        assert_eq!(pop.lineage().get(id).unwrap().parents, child.parents, "Records match the phenotype.");
+        let ancestry = pop.lineage().ancestry(id);
+        assert!(ancestry.contains(&id), "An individual is part of its own ancestry.");
+        assert!(ancestry.iter().any(|&a| pop.lineage().get(a).unwrap().birth_generation == 0), "Ancestry reaches generation 0.");
+        for a in &ancestry {
+            let record = pop.lineage().get(*a).unwrap();
+            assert!(record.parents.iter().all(|p| pop.lineage().get(*p).unwrap().birth_generation < record.birth_generation), "Parents are born before their children.");
+        }
+
+        let dot = pop.lineage().to_dot(id);
+        assert!(dot.starts_with("digraph lineage {"), "Ancestry is exported as a DOT graph.");
+        assert_eq!(dot.matches(" -> ").count(), ancestry.iter().map(|a| pop.lineage().get(*a).unwrap().parents.len()).sum::<usize>(), "Every parent gets an edge.");
+        let json = serde_json::to_string(pop.lineage()).unwrap();
+        assert_eq!(&serde_json::from_str::<Lineage>(&json).unwrap(), pop.lineage(), "Lineage survives saving.");
+        assert!(Population::new(3, 2, 10).lineage().is_empty(), "Lineage isn't tracked by default.");
+    }
 
+    #[test]
+    fn species_history() {
+        let speciation = SpeciationStrategy::new(FirstFit { threshold: 1.0 }); //tight species so some split off
+        let config = Config { track_species_history: true, speciation, ..Config::default() };
+        let mut pop = Population::with_config(3, 2, 30, config);
+        for _ in 0..15 {
+            let fitnesses = (0..30).map(|i| (i % 7) as f64).collect::<Vec<_>>();
+            pop.evolve(&fitnesses);
+        }
+
+        let history = pop.species_history();
+        assert_eq!(history.generation(0).map(|s| s.size).sum::<usize>(), 30, "Every member of a generation is in a snapshot.");
+        assert_eq!(history.generation(14).count(), pop.last_stats().unwrap().species_count, "The last generation is recorded.");
+        for snapshot in history.snapshots() {
+            let origin = history.origin(snapshot.species_id).unwrap();
+            assert!(origin.generation <= snapshot.generation, "Species exist from their origin on.");
+            if let Some(parent) = origin.parent {
+                assert!(history.origin(parent).unwrap().generation < origin.generation, "Species split off from older ones.");
+                assert!(history.children(parent).contains(&snapshot.species_id), "Children are found from their parent.");
+            }
+        }
+
+        assert!(history.snapshots().iter().any(|s| history.origin(s.species_id).unwrap().parent.is_some()), "Some species split off.");
+
+        let mut csv = vec![];
+        history.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), history.snapshots().len() + 1, "One CSV row per snapshot after the header.");
+        let svg = history.to_svg(400.0, 200.0);
+        assert!(svg.starts_with("<svg"), "Chart is an SVG.");
+        let species_count = history.snapshots().iter().map(|s| s.species_id).collect::<std::collections::HashSet<_>>().len();
+        assert_eq!(svg.matches("<polygon").count(), species_count, "One band per species.");
+        let json = serde_json::to_string(history).unwrap();
+        assert_eq!(&serde_json::from_str::<SpeciationHistory>(&json).unwrap(), history, "History survives saving.");
+        assert!(SpeciationHistory::new().to_svg(100.0, 100.0).contains("</svg>"), "Empty history still draws.");
+
+        // a steady-state child founding a species splits off from its parent's species
+        let speciation = SpeciationStrategy::new(FirstFit { threshold: 0.0 }); //every newcomer founds a species
+        let mut pop = Population::with_config(3, 2, 10, Config { track_species_history: true, speciation, ..Config::default() });
+        let species_of = pop.species.iter()
+            .flat_map(|s| s.members.iter().map(|p| (p.id.unwrap(), s.id)))
+            .collect::<HashMap<_, _>>();
+        for id in pop.ids() {
+            pop.set_fitness(id, 1.0);
+        }
+        let added = pop.replace_worst(0).unwrap().added;
+        let parent = pop.get(added).unwrap().parents[0];
+        let founded = pop.species.iter().find(|s| s.members.iter().any(|p| p.id == Some(added))).unwrap().id;
+        assert_eq!(pop.species_history().origin(founded).unwrap().parent, Some(species_of[&parent]), "Steady-state splits are recorded.");
+    }
+
+    #[test]
+    fn archipelago() {
+        for topology in [MigrationTopology::Ring, MigrationTopology::FullyConnected, MigrationTopology::Random] {
+            let mut archipelago = Archipelago::new(3, 2, 3, 20, Config::default());
+            archipelago.migration_interval = 2;
+            archipelago.topology = topology;
+            for _ in 0..6 {
+                let fitnesses = (0..3).map(|i| (0..20).map(|j| (i * 20 + j) as f64).collect()).collect::<Vec<Vec<f64>>>();
+                archipelago.evolve(&fitnesses);
+            }
+            assert_eq!(archipelago.generation(), 6, "Every island evolves together.");
+            assert!(archipelago.islands().iter().all(|i| i.ids().len() == 20), "Migration keeps island sizes.");
+
+            // one innovation history means the same innovation number is the same connection on every island
+            let mut innovations = HashMap::new();
+            for gene in archipelago.islands().iter().flat_map(|i| &i.species).flat_map(|s| &s.members).flat_map(|p| &p.genome.connection_genes) {
+                let connection = *innovations.entry(gene.innov).or_insert((gene.in_node, gene.out_node));
+                assert_eq!(connection, (gene.in_node, gene.out_node), "Innovation numbers are shared between islands.");
+            }
+        }
+
+        // the same first connections get the same innovation numbers on every island
+        let archipelago = Archipelago::new(3, 2, 4, 20, Config::default());
+        let mut connections = HashMap::new();
+        for gene in archipelago.islands().iter().flat_map(|i| &i.species).flat_map(|s| &s.members).flat_map(|p| &p.genome.connection_genes) {
+            let innov = *connections.entry((gene.in_node, gene.out_node)).or_insert(gene.innov);
+            assert_eq!(innov, gene.innov, "Islands share the first generation's innovations.");
+        }
+
+        // more immigrants than an island can take are capped
+        let mut archipelago = Archipelago::new(3, 2, 5, 8, Config::default());
+        archipelago.topology = MigrationTopology::FullyConnected;
+        archipelago.migration_interval = 1;
+        archipelago.evolve(&vec![vec![1.0; 8]; 5]);
+        assert!(archipelago.islands().iter().all(|i| i.ids().len() == 8), "Capped migration keeps island sizes.");
+
+        // the best island's champion reaches the others
+        let mut archipelago = Archipelago::new(3, 2, 2, 10, Config::default());
+        archipelago.migration_interval = 1;
+        archipelago.migrants = 1;
+        let best = archipelago.islands()[0].species.iter().flat_map(|s| &s.members).next().unwrap().genome.clone();
+        archipelago.evolve(&[vec![100.0; 1].into_iter().chain(vec![0.0; 9]).collect(), vec![0.0; 10]]);
+        assert!(archipelago.islands()[1].species.iter().flat_map(|s| &s.members).any(|p| p.genome == best), "Fittest genomes migrate.");
+        assert_eq!(archipelago.champion().unwrap().fitness, 100.0, "Champion is the best of every island.");
+
+        let path = std::env::temp_dir().join("tyrosine_archipelago_test.json");
+        archipelago.save(&path).unwrap();
+        let mut loaded = Archipelago::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.generation(), 1, "Archipelago is saved as one unit.");
+        assert_eq!(loaded.islands()[1].ids(), archipelago.islands()[1].ids(), "Islands are saved with their members.");
+        loaded.evolve(&[vec![1.0; 10], vec![1.0; 10]]);
+    }
+}
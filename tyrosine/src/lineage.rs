@@ -0,0 +1,121 @@
+use std::{collections::{BTreeMap, BTreeSet, VecDeque}, fmt::Write as _, fs::File, io::{self, BufWriter, Write}, path::Path};
+use serde::{Deserialize, Serialize};
+use crate::phenotype::{IndividualId, Phenotype};
+
+
+
+/// What's remembered about an individual after it dies
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LineageRecord {
+    pub parents: Vec<IndividualId>, //fitter parent first
+    pub birth_generation: usize,
+    pub mutations: Vec<String>,
+    pub fitness: Option<f64>, //best fitness it was ever given
+}
+
+
+
+/// Family tree of every individual born into a population
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Lineage {
+    records: BTreeMap<IndividualId, LineageRecord>,
+}
+impl Lineage {
+    pub fn new() -> Self {
+        Lineage { records: BTreeMap::new() }
+    }
+
+
+    /// Remember a newborn, it needs an id
+    pub fn record(&mut self, phenotype: &Phenotype) {
+        let id = phenotype.id.expect("Recorded individuals have an id.");
+        self.records.insert(id, LineageRecord {
+            parents: phenotype.parents.clone(),
+            birth_generation: phenotype.birth_generation,
+            mutations: phenotype.mutations.clone(),
+            fitness: phenotype.fitness,
+        });
+    }
+
+
+    /// Remember a fitness if it's the best the individual was given
+    pub fn record_fitness(&mut self, id: IndividualId, fitness: f64) {
+        if let Some(record) = self.records.get_mut(&id) && record.fitness.is_none_or(|f| fitness > f) {
+            record.fitness = Some(fitness);
+        }
+    }
+
+
+    pub fn get(&self, id: IndividualId) -> Option<&LineageRecord> {
+        self.records.get(&id)
+    }
+
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+
+    /// An individual and all of its recorded ancestors, sorted by id
+    pub fn ancestry(&self, id: IndividualId) -> BTreeSet<IndividualId> {
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::from([id]);
+        while let Some(next) = queue.pop_front() {
+            if !seen.insert(next) {
+                continue; //already reached through another parent
+            }
+            if let Some(record) = self.records.get(&next) {
+                queue.extend(record.parents.iter().copied());
+            }
+        }
+        seen.retain(|i| self.records.contains_key(i));
+        seen
+    }
+
+
+    /// Write the ancestry of an individual as a Graphviz DOT graph, edges point from parent to child
+    /// The fitter parent's edge is solid, the other parent's is dashed
+    pub fn to_dot(&self, id: IndividualId) -> String {
+        let ancestry = self.ancestry(id);
+        let mut dot = String::new();
+        writeln!(dot, "digraph lineage {{").unwrap(); //writing to a String can't fail
+        writeln!(dot, "    node [shape=box, fontname=\"Helvetica\"];").unwrap();
+        for i in &ancestry {
+            let record = &self.records[i];
+            let fitness = record.fitness.map_or("-".to_string(), |f| format!("{:.3}", f));
+            write!(dot, "    i{} [label=\"{}\\ngen {}\\nfitness {}", i.0, i.0, record.birth_generation, fitness).unwrap();
+            if !record.mutations.is_empty() {
+                write!(dot, "\\n{}", record.mutations.join(", ")).unwrap();
+            }
+            writeln!(dot, "\"{}];", if *i == id { ", style=bold" } else { "" }).unwrap();
+        }
+        for i in &ancestry {
+            for (p, parent) in self.records[i].parents.iter().enumerate().filter(|(_, p)| ancestry.contains(p)) {
+                writeln!(dot, "    i{} -> i{}{};", parent.0, i.0, if p == 0 { "" } else { " [style=dashed]" }).unwrap();
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+
+    /// Write the ancestry of an individual to a DOT file
+    pub fn export_dot<P: AsRef<Path>>(&self, path: P, id: IndividualId) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(self.to_dot(id).as_bytes())?;
+        writer.flush()
+    }
+
+
+    /// Write every record to a JSON file
+    pub fn export<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, &self.records)?;
+        writer.flush()
+    }
+}
@@ -0,0 +1,181 @@
+use std::{collections::{BTreeMap, HashMap}, fs::File, io::{self, BufReader, BufWriter, Write}, path::Path};
+use rand::{seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
+use crate::{config::{Config, InitialTopology}, genome::{Genome, GlobalInnovator}, phenotype::Phenotype};
+
+
+
+/// One dimension of the descriptor grid, values outside the range land in the edge bins
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DescriptorAxis {
+    pub min: f64,
+    pub max: f64,
+    pub bins: usize,
+}
+impl DescriptorAxis {
+    pub fn new(min: f64, max: f64, bins: usize) -> Self {
+        assert!(min < max, "Descriptor axis has a non-empty range.");
+        assert_ne!(bins, 0, "Descriptor axis has at least 1 bin.");
+        DescriptorAxis { min, max, bins }
+    }
+
+
+    /// The bin a value falls into, None for NaN
+    fn bin(&self, value: f64) -> Option<usize> {
+        if value.is_nan() {
+            return None;
+        }
+        let scaled = (value - self.min) / (self.max - self.min) * self.bins as f64;
+        Some((scaled.max(0.0) as usize).min(self.bins - 1)) //floors, and clamps anything out of range
+    }
+}
+
+
+
+/// The best genome found for one cell of the grid
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Elite {
+    pub genome: Genome,
+    pub fitness: f64,
+    pub descriptor: Vec<f64>, //exact descriptor the genome was evaluated with
+}
+
+
+
+/// Quality-diversity archive keeping the fittest genome in every cell of a descriptor grid
+/// Independent from Population, evaluate the offspring it hands out and insert them back with their descriptors
+#[derive(Serialize, Deserialize)]
+pub struct MapElites {
+    num_inputs: usize,
+    num_outputs: usize,
+    axes: Vec<DescriptorAxis>,
+    innovator: GlobalInnovator, //shared by every genome in the archive
+    cells: BTreeMap<usize, Elite>, //flattened cell index to its elite
+    pub crossover_rate: f64, //chance of an offspring having two parents instead of one
+    pub config: Config,
+}
+impl MapElites {
+    /// Create an empty archive over the given descriptor axes
+    pub fn new(num_inputs: usize, num_outputs: usize, axes: Vec<DescriptorAxis>, config: Config) -> Self {
+        assert!(!axes.is_empty(), "Archive has at least 1 descriptor axis.");
+        if let Some(adaptation) = &config.self_adaptation {
+            adaptation.assert_valid();
+        }
+        MapElites {
+            num_inputs,
+            num_outputs,
+            axes,
+            innovator: GlobalInnovator::new(),
+            cells: BTreeMap::new(),
+            crossover_rate: 0.5,
+            config,
+        }
+    }
+
+
+    /// Number of cells in the grid
+    pub fn cell_count(&self) -> usize {
+        self.axes.iter().map(|a| a.bins).product()
+    }
+
+
+    /// Flattened index of the cell a descriptor belongs to, None if any value is NaN
+    pub fn cell_index(&self, descriptor: &[f64]) -> Option<usize> {
+        assert_eq!(descriptor.len(), self.axes.len(), "Descriptor has one value per axis.");
+        self.axes.iter()
+            .zip(descriptor)
+            .try_fold(0, |index, (axis, &value)| Some(index * axis.bins + axis.bin(value)?))
+    }
+
+
+    /// Offer an evaluated genome, returns true if it became the elite of its cell
+    /// Non-finite fitness or descriptor values are turned away, they couldn't be saved
+    pub fn insert(&mut self, genome: &Genome, fitness: f64, descriptor: &[f64]) -> bool {
+        if !fitness.is_finite() || !descriptor.iter().all(|v| v.is_finite()) {
+            return false;
+        }
+        let Some(index) = self.cell_index(descriptor) else {
+            return false;
+        };
+        if self.cells.get(&index).is_some_and(|e| e.fitness >= fitness) {
+            return false; //ties keep the older elite
+        }
+        self.cells.insert(index, Elite { genome: genome.clone(), fitness, descriptor: descriptor.to_vec() });
+        true
+    }
+
+
+    /// The elite of a cell, if it has one
+    pub fn get(&self, descriptor: &[f64]) -> Option<&Elite> {
+        self.cells.get(&self.cell_index(descriptor)?)
+    }
+
+
+    /// Every elite in cell order
+    pub fn elites(&self) -> impl Iterator<Item = &Elite> {
+        self.cells.values()
+    }
+
+
+    /// The fittest elite in the archive
+    pub fn best(&self) -> Option<&Elite> {
+        self.cells.values().max_by(|x, y| x.fitness.partial_cmp(&y.fitness).unwrap_or(std::cmp::Ordering::Less))
+    }
+
+
+    /// Fraction of cells holding an elite
+    pub fn coverage(&self) -> f64 {
+        self.cells.len() as f64 / self.cell_count() as f64
+    }
+
+
+    /// Sum of every elite's fitness, only comparable across runs when fitness is never negative
+    pub fn qd_score(&self) -> f64 {
+        self.cells.values().map(|e| e.fitness).sum()
+    }
+
+
+    /// Breed new phenotypes to be evaluated, from random elites or fresh genomes while the archive is empty
+    pub fn offspring(&mut self, count: usize) -> Vec<Phenotype> {
+        let mut rng = rand::rng();
+        let mut innovations = HashMap::new(); //ensure innovation numbers are reused within the batch
+        (0..count)
+            .map(|_| {
+                let parent = match self.cells.values().choose(&mut rng) {
+                    None => {
+                        let genome = Genome::with_topology(self.num_inputs, self.num_outputs, self.config.initial_topology, &self.config, &mut self.innovator, &mut innovations);
+                        if self.config.initial_topology != InitialTopology::Minimal && let Some(phenotype) = Phenotype::from_genome(genome.clone()) {
+                            return phenotype; //wired genomes already had their weights perturbed
+                        }
+                        genome
+                    },
+                    Some(first) if self.cells.len() > 1 && rng.random::<f64>() < self.crossover_rate => {
+                        let second = self.cells.values().choose(&mut rng).unwrap(); //safe unwrap, not empty
+                        if first.fitness >= second.fitness {
+                            Genome::crossover(&first.genome, &second.genome)
+                        } else {
+                            Genome::crossover(&second.genome, &first.genome)
+                        }
+                    },
+                    Some(elite) => elite.genome.clone(),
+                };
+                Phenotype::from_mutation(&parent, &self.config, &mut self.innovator, &mut innovations)
+            })
+            .collect()
+    }
+
+
+    /// Save the whole archive to a JSON file, fails on custom operators or strategies in the config
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+
+
+    /// Load an archive previously written with save
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<MapElites> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
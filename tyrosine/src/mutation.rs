@@ -0,0 +1,291 @@
+use std::{any::Any, collections::HashMap, fmt, sync::Arc};
+use serde::{de::{Deserializer, Error as _}, ser::{Error as _, Serializer}, Deserialize, Serialize};
+use crate::{config::{Config, MutationRates}, genome::{Genome, GlobalInnovator}};
+
+
+
+/// Everything a mutation operator may need besides the genome itself
+pub struct MutationContext<'a> {
+    pub config: &'a Config,
+    pub rates: MutationRates, //the config's rates, or the genome's own when self-adaptive
+    pub innovator: &'a mut GlobalInnovator,
+    pub innovations: &'a mut HashMap<(usize, usize), usize>, //reuse innov numbers for structural innovations
+}
+
+
+
+/// A single kind of mutation, registered on the config with a weight
+/// Every call to Genome::mutate applies the operator with a chance of weight * rate
+pub trait MutationOperator: Any + Send + Sync {
+    /// Name used when debugging or comparing configs
+    fn name(&self) -> &str;
+
+    /// Base chance of being applied, built-in operators read theirs from the mutation rates
+    fn rate(&self, _rates: &MutationRates) -> f64 {
+        1.0
+    }
+
+    /// Mutate the genome in place, no need to keep it valid or its connection genes sorted
+    fn mutate(&self, genome: &mut Genome, context: &mut MutationContext);
+}
+
+
+
+/// Nudges or replaces connection weights, per-gene chances come from the mutation rates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WeightMutation;
+impl MutationOperator for WeightMutation {
+    fn name(&self) -> &str {
+        "weight"
+    }
+
+    fn mutate(&self, genome: &mut Genome, context: &mut MutationContext) {
+        genome.mutate_weights(context.config, &context.rates);
+    }
+}
+
+
+
+/// Enables or disables connections, per-gene chances come from the mutation rates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ToggleMutation;
+impl MutationOperator for ToggleMutation {
+    fn name(&self) -> &str {
+        "toggle"
+    }
+
+    fn mutate(&self, genome: &mut Genome, context: &mut MutationContext) {
+        genome.toggle_connections(&context.rates);
+    }
+}
+
+
+
+/// Switches node aggregation functions, per-node chances come from the mutation rates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AggregationMutation;
+impl MutationOperator for AggregationMutation {
+    fn name(&self) -> &str {
+        "aggregation"
+    }
+
+    fn mutate(&self, genome: &mut Genome, context: &mut MutationContext) {
+        genome.mutate_aggregations(context.config, &context.rates);
+    }
+}
+
+
+
+/// Nudges or replaces node biases when they're enabled, per-node chances come from the mutation rates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BiasMutation;
+impl MutationOperator for BiasMutation {
+    fn name(&self) -> &str {
+        "bias"
+    }
+
+    fn mutate(&self, genome: &mut Genome, context: &mut MutationContext) {
+        genome.mutate_biases(context.config, &context.rates);
+    }
+}
+
+
+
+/// Connects two unconnected nodes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AddConnectionMutation;
+impl MutationOperator for AddConnectionMutation {
+    fn name(&self) -> &str {
+        "add_connection"
+    }
+
+    fn rate(&self, rates: &MutationRates) -> f64 {
+        rates.add_connection
+    }
+
+    fn mutate(&self, genome: &mut Genome, context: &mut MutationContext) {
+        genome.add_connection(context.config, context.innovator, context.innovations);
+    }
+}
+
+
+
+/// Splits a connection with a new hidden node
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AddNodeMutation;
+impl MutationOperator for AddNodeMutation {
+    fn name(&self) -> &str {
+        "add_node"
+    }
+
+    fn rate(&self, rates: &MutationRates) -> f64 {
+        rates.add_node
+    }
+
+    fn mutate(&self, genome: &mut Genome, context: &mut MutationContext) {
+        genome.add_node(context.config, context.innovator, context.innovations);
+    }
+}
+
+
+
+/// Deletes a connection
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RemoveConnectionMutation;
+impl MutationOperator for RemoveConnectionMutation {
+    fn name(&self) -> &str {
+        "remove_connection"
+    }
+
+    fn rate(&self, rates: &MutationRates) -> f64 {
+        rates.remove_connection
+    }
+
+    fn mutate(&self, genome: &mut Genome, _context: &mut MutationContext) {
+        genome.remove_connection();
+    }
+}
+
+
+
+/// Deletes a hidden node and its connections
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RemoveNodeMutation;
+impl MutationOperator for RemoveNodeMutation {
+    fn name(&self) -> &str {
+        "remove_node"
+    }
+
+    fn rate(&self, rates: &MutationRates) -> f64 {
+        rates.remove_node
+    }
+
+    fn mutate(&self, genome: &mut Genome, _context: &mut MutationContext) {
+        genome.remove_node();
+    }
+}
+
+
+
+/// Weighted mutation operators, applied in registration order
+/// Built-in operators are saved by name with their weights, saving a custom operator fails
+#[derive(Clone)]
+pub struct MutationOperators {
+    operators: Vec<(f64, Arc<dyn MutationOperator>)>,
+}
+impl Default for MutationOperators {
+    /// The built-in operators, all with a weight of 1 so the mutation rates decide
+    fn default() -> Self {
+        let mut operators = MutationOperators::empty();
+        operators.register(1.0, WeightMutation);
+        operators.register(1.0, ToggleMutation);
+        operators.register(1.0, AggregationMutation);
+        operators.register(1.0, BiasMutation);
+        operators.register(1.0, AddConnectionMutation);
+        operators.register(1.0, AddNodeMutation);
+        operators.register(1.0, RemoveConnectionMutation);
+        operators.register(1.0, RemoveNodeMutation);
+        operators
+    }
+}
+impl MutationOperators {
+    /// No operators at all, not even the built-ins
+    pub fn empty() -> Self {
+        MutationOperators { operators: Vec::new() }
+    }
+
+
+    /// Add an operator after all existing ones
+    pub fn register<O: MutationOperator + 'static>(&mut self, weight: f64, operator: O) {
+        self.operators.push((weight, Arc::new(operator)));
+    }
+
+
+    /// Remove every operator with the given name, returns true if any were removed
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let before = self.operators.len();
+        self.operators.retain(|(_, o)| o.name() != name);
+        self.operators.len() != before
+    }
+
+
+    /// Change the weight of every operator with the given name, returns true if any were found
+    pub fn set_weight(&mut self, name: &str, weight: f64) -> bool {
+        let mut found = false;
+        for (w, _) in self.operators.iter_mut().filter(|(_, o)| o.name() == name) {
+            *w = weight;
+            found = true;
+        }
+        found
+    }
+
+
+    /// Iterate over every (weight, operator) in order
+    pub fn iter(&self) -> impl Iterator<Item = (f64, &dyn MutationOperator)> {
+        self.operators.iter().map(|(w, o)| (*w, o.as_ref()))
+    }
+}
+impl fmt::Debug for MutationOperators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.operators.iter().map(|(w, o)| (o.name(), w)))
+            .finish()
+    }
+}
+impl PartialEq for MutationOperators {
+    /// Operators are compared by name and weight
+    fn eq(&self, other: &Self) -> bool {
+        self.operators.len() == other.operators.len() &&
+            self.operators.iter().zip(&other.operators).all(|((w1, o1), (w2, o2))| w1 == w2 && o1.name() == o2.name())
+    }
+}
+impl Serialize for MutationOperators {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut saved = Vec::with_capacity(self.operators.len());
+        for (weight, operator) in &self.operators {
+            let any: &dyn Any = operator.as_ref();
+            let builtin = any.is::<WeightMutation>()
+                || any.is::<ToggleMutation>()
+                || any.is::<AggregationMutation>()
+                || any.is::<BiasMutation>()
+                || any.is::<AddConnectionMutation>()
+                || any.is::<AddNodeMutation>()
+                || any.is::<RemoveConnectionMutation>()
+                || any.is::<RemoveNodeMutation>();
+            if !builtin {
+                return Err(S::Error::custom(format!("custom mutation operator `{}` can't be saved", operator.name())));
+            }
+            saved.push(SavedOperator { name: operator.name().to_string(), weight: *weight });
+        }
+        saved.serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for MutationOperators {
+    /// Unknown operator names are an error rather than being skipped
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut operators = MutationOperators::empty();
+        for SavedOperator { name, weight } in Vec::<SavedOperator>::deserialize(deserializer)? {
+            match name.as_str() {
+                "weight" => operators.register(weight, WeightMutation),
+                "toggle" => operators.register(weight, ToggleMutation),
+                "aggregation" => operators.register(weight, AggregationMutation),
+                "bias" => operators.register(weight, BiasMutation),
+                "add_connection" => operators.register(weight, AddConnectionMutation),
+                "add_node" => operators.register(weight, AddNodeMutation),
+                "remove_connection" => operators.register(weight, RemoveConnectionMutation),
+                "remove_node" => operators.register(weight, RemoveNodeMutation),
+                _ => return Err(D::Error::custom(format!("unknown mutation operator `{}`", name))),
+            }
+        }
+        Ok(operators)
+    }
+}
+
+
+
+/// A built-in operator as it's saved
+#[derive(Serialize, Deserialize)]
+struct SavedOperator {
+    name: String,
+    weight: f64,
+}
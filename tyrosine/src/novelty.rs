@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+
+
+
+/// What an individual did while being evaluated, like its final position in a maze
+/// Behaviors are compared by Euclidean distance, so every one should have the same length
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Behavior(pub Vec<f64>);
+impl From<Vec<f64>> for Behavior {
+    fn from(values: Vec<f64>) -> Self {
+        Behavior(values)
+    }
+}
+impl Behavior {
+    /// Euclidean distance between two behaviors
+    pub fn distance(&self, other: &Behavior) -> f64 {
+        assert_eq!(self.0.len(), other.0.len(), "Behaviors being compared have the same length.");
+        self.0.iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+
+
+/// Behaviors novel enough to be remembered, new behaviors are compared against these and the current population
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct NoveltyArchive {
+    pub capacity: usize, //oldest behaviors are forgotten past this, 0 never forgets
+    behaviors: VecDeque<Behavior>,
+}
+impl NoveltyArchive {
+    pub fn new(capacity: usize) -> Self {
+        NoveltyArchive { capacity, behaviors: VecDeque::new() }
+    }
+
+
+    /// Every archived behavior, oldest first
+    pub fn behaviors(&self) -> impl Iterator<Item = &Behavior> {
+        self.behaviors.iter()
+    }
+
+
+    pub fn len(&self) -> usize {
+        self.behaviors.len()
+    }
+
+
+    pub fn is_empty(&self) -> bool {
+        self.behaviors.is_empty()
+    }
+
+
+    /// Remember a behavior, forgetting the oldest one if the archive is full
+    pub fn add(&mut self, behavior: Behavior) {
+        if self.capacity > 0 && self.behaviors.len() >= self.capacity {
+            self.behaviors.pop_front();
+        }
+        self.behaviors.push_back(behavior);
+    }
+
+
+    /// Sparseness of every behavior, the mean distance to its k nearest neighbours among the others and the archive
+    pub fn sparseness(&self, population: &[Behavior], k: usize) -> Vec<f64> {
+        let mut distances = Vec::with_capacity(population.len() + self.behaviors.len());
+        population.iter()
+            .enumerate()
+            .map(|(i, behavior)| {
+                distances.clear();
+                distances.extend(population.iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .chain(self.behaviors.iter().enumerate())
+                    .map(|(_, other)| behavior.distance(other)));
+                let k = k.min(distances.len());
+                if k == 0 {
+                    return 0.0; //nothing to compare against
+                }
+                distances.select_nth_unstable_by(k - 1, |a, b| a.total_cmp(b));
+                distances[..k].iter().sum::<f64>() / k as f64
+            })
+            .collect()
+    }
+}
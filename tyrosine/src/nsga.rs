@@ -0,0 +1,90 @@
+use std::cmp::Ordering;
+
+
+
+/// Check if a is at least as good as b in every objective and better in one, every objective is maximized
+pub fn dominates(a: &[f64], b: &[f64]) -> bool {
+    assert_eq!(a.len(), b.len(), "Both members have the same number of objectives.");
+    a.iter().zip(b).all(|(x, y)| x >= y) && a.iter().zip(b).any(|(x, y)| x > y)
+}
+
+
+
+/// Pareto front of every member, 0 is the non-dominated front
+pub fn pareto_fronts(objectives: &[Vec<f64>]) -> Vec<usize> {
+    let n = objectives.len();
+    let mut dominated_by = vec![0; n]; //number of members dominating each member
+    let mut dominating: Vec<Vec<usize>> = vec![vec![]; n]; //members each member dominates
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if dominates(&objectives[i], &objectives[j]) {
+                dominating[i].push(j);
+                dominated_by[j] += 1;
+            } else if dominates(&objectives[j], &objectives[i]) {
+                dominating[j].push(i);
+                dominated_by[i] += 1;
+            }
+        }
+    }
+
+    // peel off one front at a time
+    let mut fronts = vec![0; n];
+    let mut current: Vec<usize> = (0..n).filter(|&i| dominated_by[i] == 0).collect();
+    let mut front = 0;
+    while !current.is_empty() {
+        let mut next = vec![];
+        for &i in &current {
+            fronts[i] = front;
+            for &j in &dominating[i] {
+                dominated_by[j] -= 1;
+                if dominated_by[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        current = next;
+        front += 1;
+    }
+    fronts
+}
+
+
+
+/// Crowding distance of every member within its front, members on the edge of a front are infinitely far
+#[allow(clippy::needless_range_loop)] //objectives are indexed by member first
+pub fn crowding_distances(objectives: &[Vec<f64>], fronts: &[usize]) -> Vec<f64> {
+    let mut distances = vec![0.0; objectives.len()];
+    let objective_count = objectives.first().map_or(0, |o| o.len());
+    for front in 0..fronts.iter().max().map_or(0, |f| f + 1) {
+        let mut members: Vec<usize> = (0..objectives.len()).filter(|&i| fronts[i] == front).collect();
+        for m in 0..objective_count {
+            members.sort_by(|&a, &b| objectives[a][m].partial_cmp(&objectives[b][m]).unwrap_or(Ordering::Equal));
+            let (first, last) = (members[0], members[members.len() - 1]); //fronts are never empty
+            let range = objectives[last][m] - objectives[first][m];
+            distances[first] = f64::INFINITY;
+            distances[last] = f64::INFINITY;
+            if range <= 0.0 {
+                continue; //everyone in the front agrees on this objective
+            }
+            for w in members.windows(3) {
+                distances[w[1]] += (objectives[w[2]][m] - objectives[w[0]][m]) / range;
+            }
+        }
+    }
+    distances
+}
+
+
+
+/// Position of every member when sorted by front, then by crowding distance, 0 is the best
+pub fn ranks(objectives: &[Vec<f64>]) -> Vec<usize> {
+    let fronts = pareto_fronts(objectives);
+    let crowding = crowding_distances(objectives, &fronts);
+    let mut order: Vec<usize> = (0..objectives.len()).collect();
+    order.sort_by(|&a, &b| fronts[a].cmp(&fronts[b]).then(crowding[b].partial_cmp(&crowding[a]).unwrap_or(Ordering::Equal)));
+    let mut ranks = vec![0; objectives.len()];
+    for (rank, i) in order.into_iter().enumerate() {
+        ranks[i] = rank;
+    }
+    ranks
+}
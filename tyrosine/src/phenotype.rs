@@ -1,6 +1,12 @@
 use std::collections::{HashMap, VecDeque};
 use serde::{Deserialize, Serialize};
-use crate::genome::{ConnectionGene, Genome, GlobalInnovator};
+use crate::{config::Config, genome::{ConnectionGene, Genome, GlobalInnovator, NodeGene}};
+
+
+
+/// Stable identity of an individual, kept for as long as it survives in its Population
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IndividualId(pub usize);
 
 
 
@@ -8,15 +14,28 @@ use crate::genome::{ConnectionGene, Genome, GlobalInnovator};
 pub struct Phenotype {
     pub genome: Genome,
     pub toposorted_nodes: Vec<usize>, //ids
+    pub fitness: Option<f64>, //raw fitness from the last evaluation
+    pub adjusted_fitness: Option<f64>, //shared fitness (or novelty score) from the last evaluation, only set with fitness sharing
+    #[serde(default)]
+    pub id: Option<IndividualId>, //set once it joins a population
+    #[serde(default)]
+    pub age: usize, //generations survived, or replacements survived in steady-state evolution
+    #[serde(default)]
+    pub parents: Vec<IndividualId>, //fitter parent first, empty for the first generation
+    #[serde(default)]
+    pub birth_generation: usize,
+    #[serde(default)]
+    pub mutations: Vec<String>, //names of the mutation operators that produced it
 }
 impl Phenotype {
     /// Repeatedly mutates a genome until it gets a valid Phenotype
-    pub fn from_mutation(genome: &Genome, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) -> Phenotype {
+    pub fn from_mutation(genome: &Genome, config: &Config, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) -> Phenotype {
         loop {
             let mut gc = genome.clone();
-            gc.mutate(innovator, innovations);
+            let mutations = gc.mutate(config, innovator, innovations);
             match Phenotype::from_genome(gc) {
-                Some(x) => { //successfully generate a phenotype
+                Some(mut x) => { //successfully generate a phenotype
+                    x.mutations = mutations;
                     return x;
                 },
                 None => {
@@ -29,6 +48,13 @@ impl Phenotype {
 
     /// Generates a Phenotype from Genome and checks if it's a valid genome
     pub fn from_genome(genome: Genome) -> Option<Phenotype> {
+        Phenotype::from_genome_with(genome, false)
+    }
+
+
+    /// Generates a Phenotype from Genome, optionally only compiling nodes that feed into an output
+    /// Skipped nodes are never evaluated, which saves time on bloated genomes
+    pub fn from_genome_with(genome: Genome, outputs_only: bool) -> Option<Phenotype> {
         let mut graph: HashMap<usize, Vec<usize>> = HashMap::new(); //maps dependencies to their output
         let mut in_degree: HashMap<usize, usize> = HashMap::new(); //incoming degree of each node
 
@@ -69,9 +95,22 @@ impl Phenotype {
             return None;
         }
 
+        // inputs are always kept since activate fills them in directly
+        if outputs_only {
+            let reaching = genome.nodes_reaching_outputs();
+            sorted.retain(|id| *id < genome.num_inputs || reaching.contains(id));
+        }
+
         Some(Phenotype {
             genome,
             toposorted_nodes: sorted,
+            fitness: None,
+            adjusted_fitness: None,
+            id: None,
+            age: 0,
+            parents: vec![],
+            birth_generation: 0,
+            mutations: vec![],
         })
     }
 
@@ -86,8 +125,8 @@ impl Phenotype {
 
         // initialize input values in the nodes_values map
         node_values.insert(0, 1.0); //bias node
-        for i in 0..self.genome.num_inputs - 1 {
-            node_values.insert(i+1, inputs[i]); //offset the key to account for bias node
+        for (i, input) in inputs.iter().enumerate() {
+            node_values.insert(i+1, *input); //offset the key to account for bias node
         }
 
         // incoming connections references for each node id
@@ -98,22 +137,29 @@ impl Phenotype {
                 acc
             });
 
+        let nodes: HashMap<usize, &NodeGene> = self.genome.node_genes.iter()
+            .map(|n| (n.id, n))
+            .collect();
+
         // evaluate nodes in topological order
         // NOTE this can be optimized by pre-collecting the weights as such:
         //     HashMap<usize, Vec<(usize, f64)>> // out_node -> [(in_node, weight)]
         // but if performance is fine then don't bother
+        let mut weighted = Vec::new(); //reused between nodes
         for &node_id in &self.toposorted_nodes {
             if node_values.contains_key(&node_id) {
                 continue; //node value already initialized, i do wonder if this is an error
             }
 
-            let sum: f64 = incoming.get(&node_id)
+            weighted.clear();
+            weighted.extend(incoming.get(&node_id)
                 .unwrap_or(&vec![])
                 .iter()
-                .map(|conn| node_values.get(&conn.in_node).unwrap_or(&0.0) * conn.weight)
-                .sum();
+                .map(|conn| node_values.get(&conn.in_node).unwrap_or(&0.0) * conn.weight));
 
-            node_values.insert(node_id, sum.tanh());
+            let node = nodes[&node_id];
+            let aggregated = node.aggregation.aggregate(&mut weighted);
+            node_values.insert(node_id, (aggregated + node.bias).tanh());
         }
 
         let output_start = self.genome.num_inputs; //outputs start right after the inputs
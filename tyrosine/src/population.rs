@@ -1,14 +1,54 @@
-use std::{collections::{HashMap, HashSet}, mem};
-use rand::seq::{IndexedRandom, SliceRandom};
-use crate::{genome::{Genome, GlobalInnovator}, phenotype::Phenotype, species::{Species, SpeciesCounter}};
+use std::{collections::{BTreeSet, HashMap, HashSet}, fs::File, io::{self, BufReader, BufWriter, Write}, mem, path::Path};
+use rand::{distr::{weighted::WeightedIndex, Distribution}, seq::{IndexedRandom, SliceRandom}, Rng};
+use serde::{Deserialize, Serialize};
+use crate::{config::{Config, InitialTopology, MutationRates, SearchPhase}, genome::{Genome, GlobalInnovator}, hall_of_fame::{HallOfFame, HallOfFameEntry}, history::SpeciationHistory, lineage::Lineage, novelty::{Behavior, NoveltyArchive}, nsga, phenotype::{IndividualId, Phenotype}, reporter::{GenerationStats, Reporter, SpeciesChange}, species::{Species, SpeciesCounter}};
 
 
 
+/// The individuals swapped by one steady-state replacement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Replacement {
+    pub removed: IndividualId,
+    pub added: IndividualId,
+}
+
+
+
+#[derive(Serialize, Deserialize)]
 pub struct Population {
     generation_number: usize,
-    innovator: GlobalInnovator,
+    pub(crate) innovator: GlobalInnovator, //swapped in and out by an Archipelago so every island shares one
     species_counter: SpeciesCounter,
-    index_cache: HashMap<usize, (usize, usize)>,
+    #[serde(skip)]
+    index_cache: HashMap<usize, (usize, usize)>, //rebuilt when loading
+    #[serde(skip)]
+    reporters: Vec<Box<dyn Reporter>>, //reporters aren't saved, register them again after loading
+    #[serde(skip)]
+    steady_innovations: HashMap<(usize, usize), usize>, //innovations of recent steady-state children, reset every population_size replacements
+    #[serde(skip)]
+    replacements: usize, //steady-state replacements since steady_innovations was reset
+    #[serde(default)]
+    next_individual: usize, //id of the next individual to be born
+    best_fitness: Option<f64>, //best fitness seen across all generations
+    #[serde(default)]
+    novelty_archive: NoveltyArchive,
+    #[serde(default)]
+    lineage: Lineage, //empty unless config.track_lineage is set
+    #[serde(default)]
+    species_history: SpeciationHistory, //empty unless config.track_species_history is set
+    #[serde(default)]
+    stagnant_generations: usize, //generations since best_fitness last improved
+    last_stats: Option<GenerationStats>,
+    hall_of_fame: HallOfFame,
+    #[serde(default)]
+    search_phase: SearchPhase,
+    #[serde(default)]
+    complexity_floor: f64, //mean complexity when the last simplifying phase ended
+    #[serde(default)]
+    lowest_complexity: f64, //lowest mean complexity seen during the current simplifying phase
+    #[serde(default)]
+    stalled_generations: usize, //generations since lowest_complexity last dropped
+    pub config: Config,
     pub species: Vec<Species>,
     pub population_size: usize,
     //pub num_inputs: usize,
@@ -17,25 +57,42 @@ pub struct Population {
 impl Population {
     /// Create a new population of genomes
     pub fn new(num_inputs: usize, num_outputs: usize, population_size: usize) -> Self {
-        let mut innovator = GlobalInnovator::new();
+        Population::with_config(num_inputs, num_outputs, population_size, Config::default())
+    }
+
+
+    /// Create a new population of genomes with custom parameters
+    pub fn with_config(num_inputs: usize, num_outputs: usize, population_size: usize, config: Config) -> Self {
+        Population::with_innovator(num_inputs, num_outputs, population_size, config, GlobalInnovator::new(), &mut HashMap::new())
+    }
+
+
+    /// Create a new population continuing an existing innovation history
+    /// Innovations are shared so structure created alongside other populations gets the same innovation numbers
+    pub(crate) fn with_innovator(num_inputs: usize, num_outputs: usize, population_size: usize, config: Config, mut innovator: GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) -> Self {
+        if let Some(adaptation) = &config.self_adaptation {
+            adaptation.assert_valid();
+        }
         let mut species_counter = SpeciesCounter::new();
 
         // initialize
-        let population = (0..population_size).into_iter()
-            .map(|_| Genome::new(num_inputs, num_outputs))
+        let population = (0..population_size)
+            .map(|_| Genome::with_topology(num_inputs, num_outputs, config.initial_topology, &config, &mut innovator, innovations))
             .collect::<Vec<Genome>>();
 
-        // mutate
-        let mut innovations = HashMap::new(); //ensure innovation numbers are reused
+        // mutate blank genomes so they have something to work with, wired ones already had their weights perturbed
         let mutated_population = population.into_iter()
-            .map(|genome| Phenotype::from_mutation(&genome, &mut innovator, &mut innovations))
+            .map(|genome| match config.initial_topology {
+                InitialTopology::Minimal => Phenotype::from_mutation(&genome, &config, &mut innovator, innovations),
+                _ => Phenotype::from_genome(genome).unwrap(), //safe unwrap, initial topologies are feed-forward
+            })
             .collect::<Vec<Phenotype>>();
 
         // assign species
         let mut rng = rand::rng();
         let chosen = mutated_population.choose(&mut rng).unwrap(); //safe unwrap
         let mut species = vec![Species::new(&chosen.genome, species_counter.next())];
-        Species::sort_species(&mut species, mutated_population, &mut species_counter);
+        config.speciation.speciate(&mut species, mutated_population, &mut species_counter, &config);
         
         let mut population = Population {
             generation_number: 0,
@@ -46,25 +103,55 @@ impl Population {
             //num_inputs,
             //num_outputs,
             index_cache: HashMap::with_capacity(population_size),
+            reporters: Vec::new(),
+            steady_innovations: HashMap::new(),
+            replacements: 0,
+            next_individual: 0,
+            best_fitness: None,
+            novelty_archive: NoveltyArchive::new(config.novelty.map_or(0, |n| n.archive_capacity)),
+            lineage: Lineage::new(),
+            species_history: SpeciationHistory::new(),
+            stagnant_generations: 0,
+            last_stats: None,
+            hall_of_fame: HallOfFame::new(config.hall_of_fame_size),
+            search_phase: SearchPhase::Complexifying,
+            complexity_floor: 0.0,
+            lowest_complexity: 0.0,
+            stalled_generations: 0,
+            config,
         };
+        population.assign_ids(0);
         population.update_cache(); //easy indexing
 
         population
     }
 
 
+    /// Give every member without an id a new one, they're recorded as born in the given generation
+    fn assign_ids(&mut self, birth_generation: usize) {
+        for phenotype in self.species.iter_mut().flat_map(|s| &mut s.members).filter(|p| p.id.is_none()) {
+            phenotype.id = Some(IndividualId(self.next_individual));
+            phenotype.birth_generation = birth_generation;
+            self.next_individual += 1;
+            if self.config.track_lineage {
+                self.lineage.record(phenotype);
+            }
+        }
+    }
+
+
     /// Update index cache to speed up phenotype indexing
     pub fn update_cache(&mut self) {
         // ensure all species have at least one member before starting
         for s in &self.species {
-            assert!(s.members.len() > 0, "All species have at least one member before caching.");
+            assert!(!s.members.is_empty(), "All species have at least one member before caching.");
         }
 
         let mut population_offset = 0;
         self.index_cache.clear(); //apparently nearly no slowdown, safer this way
 
         for (i, s) in self.species.iter().enumerate() {
-            self.index_cache.extend((0..s.members.len()).into_iter()
+            self.index_cache.extend((0..s.members.len())
                 .map(|x| (population_offset+x, (i, x))) //convert member index to (global index, (species index, member index))
             );
             population_offset += s.members.len();
@@ -80,6 +167,89 @@ impl Population {
     }
 
 
+    /// Register a reporter to be notified while evolving
+    pub fn add_reporter<R: Reporter + 'static>(&mut self, reporter: R) {
+        self.reporters.push(Box::new(reporter));
+    }
+
+
+    /// Statistics of the most recently evaluated generation
+    pub fn last_stats(&self) -> Option<&GenerationStats> {
+        self.last_stats.as_ref()
+    }
+
+
+    /// The best genome ever evaluated, along with its fitness and generation
+    pub fn champion(&self) -> Option<&HallOfFameEntry> {
+        self.hall_of_fame.champion()
+    }
+
+
+    /// The best distinct genomes ever evaluated
+    pub fn hall_of_fame(&self) -> &HallOfFame {
+        &self.hall_of_fame
+    }
+
+
+    /// Save the whole population to a JSON file, reporters aren't saved
+    /// Fails if the config has custom mutation operators or a custom speciation strategy
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+
+
+    /// Load a population previously written with save
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Population> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut population: Population = serde_json::from_reader(reader)?;
+        population.restore()?;
+        Ok(population)
+    }
+
+
+    /// Rebuild what isn't saved after deserializing, species that don't add up to the population are invalid data
+    pub(crate) fn restore(&mut self) -> io::Result<()> {
+        if self.species.iter().any(|s| s.members.is_empty()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "every species has at least one member"));
+        }
+        let members = self.species.iter().map(|s| s.members.len()).sum::<usize>();
+        if members != self.population_size {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} members don't match the population size of {}", members, self.population_size)));
+        }
+        self.assign_ids(self.generation_number); //saves from before ids existed
+        self.update_cache();
+        Ok(())
+    }
+
+
+    /// Whether a phased search is currently growing or shrinking genomes
+    pub fn search_phase(&self) -> SearchPhase {
+        self.search_phase
+    }
+
+
+    /// Generations evolved since the best fitness last improved
+    pub fn stagnant_generations(&self) -> usize {
+        self.stagnant_generations
+    }
+
+
+    /// The number of generations evolved so far
+    pub fn generation(&self) -> usize {
+        self.generation_number
+    }
+
+
+    /// Notify all reporters that the run is over
+    pub fn end_run(&mut self) {
+        for reporter in &mut self.reporters {
+            reporter.run_end(self.generation_number);
+        }
+    }
+
+
     ///// Feed the input and generate an output for a particular index in the population
     //pub fn activate_index(&self, idx: usize, input: &mut Vec<f64>) -> Option<Vec<f64>> {
     //    let pair = self.index_cache.get(&idx)?; //will fail if user provided number larger than the population size
@@ -89,7 +259,7 @@ impl Population {
 
 
     /// Feed the input and generate an output for a particular index in the population
-    pub fn activate_index(&self, idx: usize, input: &mut Vec<f64>) -> Vec<f64> {
+    pub fn activate_index(&self, idx: usize, input: &mut [f64]) -> Vec<f64> {
         assert!(idx < self.population_size, "User provided valid index for activation.");
         let pair = self.index_cache.get(&idx).unwrap(); //will fail if user provided number larger than the population size
         let phenotype = &self.species[pair.0].members[pair.1];
@@ -117,16 +287,367 @@ impl Population {
     }
 
 
+    /// Shares in proportion to the values, shifted so the lowest is 0 only when some are negative
+    /// Non-finite values get no share
+    pub(crate) fn proportional_shares(values: &[f64]) -> Vec<f64> {
+        let lowest = values.iter().cloned().filter(|v| v.is_finite()).fold(0.0, f64::min);
+        values.iter().map(|v| if v.is_finite() { v - lowest } else { 0.0 }).collect()
+    }
+
+
+    /// Move slots from the species with the most slots to protected species under their minimum
+    fn protect_young_species(slots: &mut [usize], species: &[Species], config: &Config) {
+        let minimum = |i: usize| if species[i].is_protected(config) { config.protected_species_slots } else { 0 };
+        for i in 0..slots.len() {
+            while slots[i] < minimum(i) {
+                // take from whichever species has the most to spare
+                let donor = (0..slots.len())
+                    .filter(|&j| j != i && slots[j] > minimum(j).max(1))
+                    .max_by_key(|&j| slots[j]);
+                match donor {
+                    Some(j) => {
+                        slots[j] -= 1;
+                        slots[i] += 1;
+                    },
+                    None => break, //nobody can spare a slot
+                }
+            }
+        }
+    }
+
+
+    /// Switch between complexifying and simplifying based on the mean complexity of the population
+    fn update_search_phase(&mut self, mean_complexity: f64) {
+        let Some(phased) = self.config.phased_search else {
+            return;
+        };
+
+        match self.search_phase {
+            SearchPhase::Complexifying => {
+                if mean_complexity > self.complexity_floor + phased.complexity_threshold {
+                    self.search_phase = SearchPhase::Simplifying;
+                    self.lowest_complexity = mean_complexity;
+                    self.stalled_generations = 0;
+                }
+            },
+            SearchPhase::Simplifying => {
+                if mean_complexity < self.lowest_complexity {
+                    self.lowest_complexity = mean_complexity;
+                    self.stalled_generations = 0;
+                } else {
+                    self.stalled_generations += 1;
+                }
+                if self.stalled_generations >= phased.stall_generations {
+                    self.search_phase = SearchPhase::Complexifying;
+                    self.complexity_floor = mean_complexity;
+                }
+            },
+        }
+    }
+
+
+    /// The config used to breed the next generation, with any scheduled changes applied
+    pub(crate) fn generation_config(&self) -> Config {
+        let mut config = self.config.clone();
+        if let Some(phased) = self.config.phased_search && self.search_phase == SearchPhase::Simplifying {
+            config.mutation.add_connection = 0.0;
+            config.mutation.add_node = 0.0;
+            config.mutation.remove_connection = phased.remove_connection;
+            config.mutation.remove_node = phased.remove_node;
+        }
+
+        // annealing schedules
+        let power = config.mutation_power_schedule.multiplier(self.generation_number, self.stagnant_generations);
+        config.mutation.perturbation_std = (config.mutation.perturbation_std * power).max(0.0); //schedules can overshoot below 0
+        config.mutation.bias_perturbation_std = (config.mutation.bias_perturbation_std * power).max(0.0);
+        let structural = config.structural_schedule.multiplier(self.generation_number, self.stagnant_generations);
+        for rate in [
+            &mut config.mutation.add_connection,
+            &mut config.mutation.add_node,
+            &mut config.mutation.remove_connection,
+            &mut config.mutation.remove_node,
+        ] {
+            *rate = (*rate * structural).clamp(0.0, 1.0);
+        }
+        config
+    }
+
+
     /// Evolve the population by one generation with provided fitness
     /// NOTE the order of specimens received to calculate fitness is the same order here
     /// TODO should implement an error class, could communicate fixable errors to the user like mismatched fitness size
-    pub fn evolve(&mut self, fitnesses: &Vec<f64>) {
+    pub fn evolve(&mut self, fitnesses: &[f64]) {
+        self.evolve_sharing(fitnesses, &mut HashMap::new());
+    }
+
+
+    /// Evolve by fitness, numbering new structure alike with everything else bred from the same innovations
+    pub(crate) fn evolve_sharing(&mut self, fitnesses: &[f64], innovations: &mut HashMap<(usize, usize), usize>) {
+        self.evolve_scored(fitnesses, fitnesses, innovations);
+    }
+
+
+    /// Evolve with novelty search, members are selected on how novel their behaviors are, or a blend with fitness
+    /// Fitness is still what the hall of fame, the champion and the stats track
+    pub fn evolve_with_behaviors(&mut self, fitnesses: &[f64], behaviors: &[Behavior]) {
+        let Some(novelty) = self.config.novelty else {
+            panic!("Novelty search is configured before evolving with behaviors.");
+        };
+        assert_eq!(behaviors.len(), self.population_size, "Behaviors count and population size match.");
+        let sparseness = self.novelty_archive.sparseness(behaviors, novelty.k);
+        let scores = fitnesses.iter()
+            .zip(&sparseness)
+            .map(|(fitness, novelty_score)| novelty.novelty_weight * novelty_score + (1.0 - novelty.novelty_weight) * fitness)
+            .collect::<Vec<_>>();
+
+        // archive after scoring so everyone is compared to the same past
+        let mut rng = rand::rng();
+        self.novelty_archive.capacity = novelty.archive_capacity;
+        for (behavior, &novelty_score) in behaviors.iter().zip(&sparseness) {
+            if novelty_score > novelty.archive_threshold || rng.random::<f64>() < novelty.archive_chance {
+                self.novelty_archive.add(behavior.clone());
+            }
+        }
+
+        self.evolve_scored(fitnesses, &scores, &mut HashMap::new());
+    }
+
+
+    /// Behaviors remembered by novelty search
+    pub fn novelty_archive(&self) -> &NoveltyArchive {
+        &self.novelty_archive
+    }
+
+
+    /// Family tree of every individual born since lineage tracking was enabled
+    pub fn lineage(&self) -> &Lineage {
+        &self.lineage
+    }
+
+
+    /// Every species of every generation evolved since species history tracking was enabled
+    pub fn species_history(&self) -> &SpeciationHistory {
+        &self.species_history
+    }
+
+
+    /// Every member's id, in the same order as activate_index
+    pub fn ids(&self) -> Vec<IndividualId> {
+        self.species.iter()
+            .flat_map(|s| &s.members)
+            .map(|p| p.id.unwrap()) //safe unwrap, every member has an id
+            .collect()
+    }
+
+
+    /// The member with the given id, if it's still alive
+    pub fn get(&self, id: IndividualId) -> Option<&Phenotype> {
+        self.species.iter()
+            .flat_map(|s| &s.members)
+            .find(|p| p.id == Some(id))
+    }
+
+
+    /// Record the fitness of a member for steady-state evolution, returns false if it's no longer alive
+    pub fn set_fitness(&mut self, id: IndividualId, fitness: f64) -> bool {
+        let Some(phenotype) = self.species.iter_mut().flat_map(|s| &mut s.members).find(|p| p.id == Some(id)) else {
+            return false;
+        };
+        phenotype.fitness = Some(fitness);
+        if self.config.track_lineage {
+            self.lineage.record_fitness(id, fitness);
+        }
+        if self.hall_of_fame.qualifies(fitness) {
+            self.hall_of_fame.offer(&phenotype.genome, fitness, self.generation_number);
+        }
+        true
+    }
+
+
+    /// Steady-state evolution as in rtNEAT, every member ages by one with each call
+    /// Removes the member with the lowest adjusted fitness among those at least eligible_min_age old with a fitness,
+    /// then breeds one child from a species picked in proportion to its average fitness and speciates it in place
+    /// Returns None if nobody was eligible, nothing is replaced then
+    pub fn replace_worst(&mut self, eligible_min_age: usize) -> Option<Replacement> {
+        assert!(self.population_size > 1, "Steady-state evolution needs at least 2 members.");
+        for phenotype in self.species.iter_mut().flat_map(|s| &mut s.members) {
+            phenotype.age += 1;
+        }
+
+        // fitness is shared by species size so big species can't crowd out small ones
+        let worst = self.species.iter()
+            .enumerate()
+            .flat_map(|(s_i, s)| s.members.iter().enumerate().map(move |(m_i, p)| (s_i, m_i, p, s.members.len())))
+            .filter(|(_, _, p, _)| p.age >= eligible_min_age)
+            .filter_map(|(s_i, m_i, p, size)| Some((s_i, m_i, p.fitness? / size as f64)))
+            .min_by(|x, y| x.2.partial_cmp(&y.2).unwrap_or(std::cmp::Ordering::Less));
+        let (s_i, m_i, _) = worst?;
+        let removed = self.species[s_i].members.remove(m_i);
+        if self.species[s_i].members.is_empty() {
+            self.species.remove(s_i);
+        }
+
+        // species with better average fitness are more likely to be the parents, unevaluated members don't count
+        let averages = self.species.iter()
+            .map(|s| {
+                let fitnesses = s.members.iter().filter_map(|p| p.fitness).collect::<Vec<_>>();
+                fitnesses.iter().sum::<f64>() / fitnesses.len().max(1) as f64
+            })
+            .collect::<Vec<_>>();
+        let mut rng = rand::rng();
+        let parent = match WeightedIndex::new(Population::proportional_shares(&averages)) {
+            Ok(weights) => weights.sample(&mut rng),
+            Err(_) => rng.random_range(0..self.species.len()), //no usable fitness, pick uniformly
+        };
+
+        // innovations are shared by a generation's worth of children
+        if self.replacements >= self.population_size {
+            self.steady_innovations.clear();
+            self.replacements = 0;
+        }
+        self.replacements += 1;
+        let config = self.generation_config();
+        let mut child = self.species[parent].breed_one(&config, &mut self.innovator, &mut self.steady_innovations);
+        let added = IndividualId(self.next_individual);
+        self.next_individual += 1;
+        child.id = Some(added);
+        child.birth_generation = self.generation_number;
+        if self.config.track_lineage {
+            self.lineage.record(&child);
+        }
+        let parent_species = self.species[parent].id;
+        let known: BTreeSet<usize> = self.species.iter().map(|s| s.id).collect();
+        config.speciation.speciate_one(&mut self.species, child, &mut self.species_counter, &config);
+        if self.config.track_species_history {
+            // a child that founds a new species split off from its parents' species
+            for spec in self.species.iter().filter(|s| !known.contains(&s.id)) {
+                self.species_history.record_split(spec.id, Some(parent_species), self.generation_number);
+            }
+        }
+        self.update_cache();
+
+        Some(Replacement {
+            removed: removed.id.unwrap(), //safe unwrap, every member has an id
+            added,
+        })
+    }
+
+
+    /// Replace random members with immigrant genomes and sort them into species, call this before evaluating
+    /// Children that haven't been evaluated are replaced first, then anyone but the other immigrants
+    pub fn immigrate(&mut self, genomes: &[Genome]) {
+        assert!(genomes.len() < self.population_size, "Fewer immigrants than members.");
+        let config = self.generation_config();
+        let mut rng = rand::rng();
+        let known: BTreeSet<usize> = self.species.iter().map(|s| s.id).collect();
+        for genome in genomes {
+            let Some(immigrant) = Phenotype::from_genome(genome.clone()) else {
+                continue; //only feed-forward genomes can settle
+            };
+            let members = self.species.iter()
+                .enumerate()
+                .flat_map(|(s_i, s)| s.members.iter().enumerate().map(move |(m_i, p)| (s_i, m_i, p)))
+                .filter(|(_, _, p)| p.id.is_some()) //immigrants get their ids once all have settled
+                .collect::<Vec<_>>();
+            let children = members.iter().filter(|(_, _, p)| p.fitness.is_none()).collect::<Vec<_>>();
+            let pool = if children.is_empty() { members.iter().collect() } else { children };
+            let &&(s_i, m_i, _) = pool.choose(&mut rng).unwrap(); //safe unwrap, more members than immigrants
+            self.species[s_i].members.remove(m_i);
+            if self.species[s_i].members.is_empty() {
+                self.species.remove(s_i);
+            }
+            config.speciation.speciate_one(&mut self.species, immigrant, &mut self.species_counter, &config);
+        }
+        if self.config.track_species_history {
+            // immigrants have no parents on this island, so their new species are roots, but they're recorded as arriving now
+            for spec in self.species.iter().filter(|s| !known.contains(&s.id)) {
+                self.species_history.record_split(spec.id, None, self.generation_number);
+            }
+        }
+        self.assign_ids(self.generation_number);
+        self.update_cache();
+    }
+
+
+    /// Evolve with several objectives per member, every objective is maximized
+    /// Members are ranked within their species by Pareto front and crowding distance, slots come from population-wide fronts
+    /// The first objective is treated as the fitness for the hall of fame, the champion and the stats
+    /// Parsimony pressure isn't applied to the objectives, use the complexity objective to favour smaller networks
+    pub fn evolve_multi(&mut self, objectives: &[Vec<f64>]) {
+        assert_eq!(objectives.len(), self.population_size, "Objectives count and population size match.");
+        let mut objectives = objectives.to_vec();
+        if self.config.complexity_objective {
+            for (i, objective) in objectives.iter_mut().enumerate() {
+                let (s_i, m_i) = self.index_cache[&i];
+                objective.push(-(self.species[s_i].members[m_i].genome.complexity() as f64)); //smaller is better
+            }
+        }
+        let fitnesses = objectives.iter()
+            .map(|o| o.first().copied().unwrap_or(0.0))
+            .collect::<Vec<_>>();
+
+        // members in better fronts across the whole population earn their species more slots
+        let fronts = nsga::pareto_fronts(&objectives);
+        let front_count = fronts.iter().max().map_or(0, |f| f + 1);
+        let scores = fronts.iter()
+            .map(|f| (front_count - f) as f64)
+            .collect::<Vec<_>>();
+
+        // rank each species on its own
+        let mut ranks = vec![0; self.population_size];
+        let mut offset = 0;
+        for spec in &self.species {
+            let species_objectives = &objectives[offset..offset + spec.members.len()]; //the cache numbers members species by species
+            for (i, rank) in nsga::ranks(species_objectives).into_iter().enumerate() {
+                ranks[offset + i] = rank;
+            }
+            offset += spec.members.len();
+        }
+
+        self.evolve_ranked(&fitnesses, &scores, Some(&ranks), &mut HashMap::new());
+    }
+
+
+    /// Evolve using scores for selection, fitness is only recorded
+    /// Parsimony pressure is applied to the scores here
+    fn evolve_scored(&mut self, fitnesses: &[f64], scores: &[f64], innovations: &mut HashMap<(usize, usize), usize>) {
+        match self.config.parsimony {
+            Some(parsimony) => {
+                let penalized = scores.iter()
+                    .enumerate()
+                    .map(|(i, score)| {
+                        let (s_i, m_i) = self.index_cache[&i];
+                        score - parsimony.penalty(&self.species[s_i].members[m_i].genome)
+                    })
+                    .collect::<Vec<_>>();
+                self.evolve_ranked(fitnesses, &penalized, None, innovations);
+            },
+            None => self.evolve_ranked(fitnesses, scores, None, innovations),
+        }
+    }
+
+
+    /// Evolve using scores for allotting slots, members are sorted by their rank within the species if given, or their score
+    fn evolve_ranked(&mut self, fitnesses: &[f64], scores: &[f64], ranks: Option<&[usize]>, innovations: &mut HashMap<(usize, usize), usize>) {
         assert_eq!(fitnesses.len(), self.population_size, "Fitnesses count and population size match.");
+        assert_eq!(scores.len(), self.population_size, "Scores count and population size match.");
+        for reporter in &mut self.reporters {
+            reporter.generation_start(self.generation_number);
+        }
+        let previous_ids: BTreeSet<usize> = self.species.iter().map(|s| s.id).collect();
+        let previous_species: HashMap<IndividualId, usize> = if self.config.track_species_history {
+            self.species.iter()
+                .flat_map(|s| s.members.iter().filter_map(move |p| Some((p.id?, s.id))))
+                .collect()
+        } else {
+            HashMap::new() //only needed to find where new species split off from
+        };
 
         let fitness_by_species_index = fitnesses.iter()
+            .zip(scores)
             .enumerate()
-            .map(|(i, x)| (*self.index_cache.get(&i).unwrap(), *x))
-            .collect::<Vec<((usize, usize), f64)>>();
+            .map(|(i, (x, score))| (*self.index_cache.get(&i).unwrap(), (*x, *score, ranks.map(|r| r[i]))))
+            .collect::<Vec<((usize, usize), (f64, f64, Option<usize>))>>();
 
         // refactor to a list of lists
         let mut fitness_by_species = vec![vec![]; self.species.len()];
@@ -140,30 +661,106 @@ impl Population {
             }
         }
 
-        let mut total_fitness = 0.0;
-
         // sort all phenotypes within their species and calculate species fitnesses
         assert_eq!(self.species.len(), fitness_by_species.len(), "Ensure lengths of self.species and fitness_by_species are the same.");
         for (spec, fits) in self.species.iter_mut().zip(fitness_by_species) {
             assert_ne!(spec.members.len(), 0, "All species have at least 1 member before calculating fitness.");
             assert_eq!(spec.members.len(), fits.len(), "Ensure number of species members and fitnesses for this species are the same.");
             let mut zipped: Vec<_> = spec.members.drain(..).zip(fits).collect();
-            zipped.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap_or(std::cmp::Ordering::Less));
+            match ranks {
+                Some(_) => zipped.sort_by_key(|x| x.1.2),
+                None => zipped.sort_by(|x, y| y.1.1.partial_cmp(&x.1.1).unwrap_or(std::cmp::Ordering::Less)), //by score
+            }
             let (phens, fits): (Vec<_>, Vec<_>) = zipped.into_iter().unzip();
             spec.members = phens;
-            spec.species_fitness = Some(fits.iter().sum::<f64>() / fits.len() as f64);
-            total_fitness += spec.species_fitness.unwrap(); //safe unwrap
+            spec.best_fitness = fits.iter().map(|f| f.0).reduce(f64::max);
+
+            // remember the best genomes ever seen, checking before cloning anything
+            for (phen, &(fitness, _, _)) in spec.members.iter().zip(&fits) {
+                if self.hall_of_fame.qualifies(fitness) {
+                    self.hall_of_fame.offer(&phen.genome, fitness, self.generation_number);
+                }
+            }
+
+            // explicit fitness sharing divides by the species size, which sums to the same species mean
+            let species_size = fits.len() as f64;
+            for (phen, &(fitness, score, _)) in spec.members.iter_mut().zip(&fits) {
+                phen.fitness = Some(fitness);
+                if self.config.track_lineage {
+                    self.lineage.record_fitness(phen.id.unwrap(), fitness); //safe unwrap, every member has an id
+                }
+                phen.adjusted_fitness = self.config.fitness_sharing.then_some(score / species_size);
+            }
+            let species_fitness = if self.config.fitness_sharing {
+                spec.members.iter().map(|p| p.adjusted_fitness.unwrap()).sum::<f64>() //safe unwrap, set above
+            } else {
+                fits.iter().map(|f| f.1).sum::<f64>() / species_size
+            };
+            spec.species_fitness = Some(species_fitness * spec.age_multiplier(&self.config)); //boost young species, penalize old ones
         }
 
         for s in &self.species {
             assert_ne!(s.members.len(), 0, "All species have at least 1 member before allotting slots.");
         }
 
-        // TODO when we implement recording a generation, do it here after all the sorting is done
+        // record the generation now that all the sorting is done
+        if self.config.track_species_history {
+            self.species_history.record(self.generation_number, &self.species);
+        }
+        let mut stats = GenerationStats::collect(self.generation_number, &self.species, fitnesses, self.innovator.innov);
+        if let Some(adaptation) = &self.config.self_adaptation {
+            let rates = self.generation_config().mutation;
+            stats.mean_mutation_rates = MutationRates::mean(self.species.iter()
+                .flat_map(|s| &s.members)
+                .filter_map(|p| p.genome.rate_factors.as_ref())
+                .map(|f| f.apply(&rates, adaptation)));
+        }
+        if let Some(parsimony) = &self.config.parsimony {
+            let penalized = self.species.iter()
+                .flat_map(|s| &s.members)
+                .map(|p| p.fitness.unwrap() - parsimony.penalty(&p.genome)) //safe unwrap, set above
+                .collect::<Vec<_>>();
+            stats.best_penalized_fitness = penalized.iter().cloned().reduce(f64::max);
+            stats.mean_penalized_fitness = Some(penalized.iter().sum::<f64>() / penalized.len().max(1) as f64);
+        }
+        for reporter in &mut self.reporters {
+            reporter.post_evaluate(&stats);
+        }
+        self.last_stats = Some(stats);
+
+        // check if anyone beat the best so far, the elite of each species is only the fittest when selecting on fitness
+        let generation_best = self.species.iter()
+            .flat_map(|s| &s.members)
+            .map(|p| (p, p.fitness.unwrap())) //safe unwrap, set above
+            .max_by(|x, y| x.1.partial_cmp(&y.1).unwrap_or(std::cmp::Ordering::Less));
+        if let Some((champion, fitness)) = generation_best && self.best_fitness.is_none_or(|best| fitness > best) {
+            self.best_fitness = Some(fitness);
+            self.stagnant_generations = 0;
+            for reporter in &mut self.reporters {
+                reporter.new_champion(self.generation_number, champion, fitness);
+            }
+        } else {
+            self.stagnant_generations += 1;
+        }
 
-        // now we commence natural selection
-        let mut reproductive_slots: Vec<_> = self.species.iter()
-            .map(|s| (s.species_fitness.unwrap() / total_fitness * self.population_size as f64) as usize) //floors
+        // decide whether the next generation should grow or shrink
+        let complexity: usize = self.species.iter()
+            .flat_map(|s| &s.members)
+            .map(|p| p.genome.complexity())
+            .sum();
+        self.update_search_phase(complexity as f64 / self.population_size as f64);
+        let config = self.generation_config();
+
+        // now we commence natural selection, shifting species fitness so the lowest share is 0 when any are negative
+        let species_fitnesses = self.species.iter().map(|s| s.species_fitness.unwrap()).collect::<Vec<_>>(); //safe unwrap, set above
+        let shares = Population::proportional_shares(&species_fitnesses);
+        let total_share: f64 = shares.iter().sum();
+        let mut reproductive_slots: Vec<_> = shares.iter()
+            .map(|share| if total_share > 0.0 && total_share.is_finite() {
+                (share / total_share * self.population_size as f64) as usize //floors
+            } else {
+                0 //nothing to tell species apart, the remainder splits the population evenly
+            })
             .collect();
 
         // see how many slots we have total, and adjust to ensure we have self.population_size
@@ -177,35 +774,60 @@ impl Population {
             }
         }
 
+        // young species get a guaranteed minimum so their new structure has time to be tuned
+        Population::protect_young_species(&mut reproductive_slots, &self.species, &self.config);
+
         // kill off species with 0 reproductive slots so they don't reproduce
         let species = mem::take(&mut self.species); //maybe this can be done differently, empties self.species for now
         let (reproductive_slots, mut species): (Vec<_>, Vec<_>) = reproductive_slots.into_iter()
             .zip(species)
-            .filter(|(slots, _)| *slots != 0 as usize)
+            .filter(|(slots, _)| *slots != 0)
             .collect();
 
         // for each species, kill off 50%, choose a new type specimen, and return a new generation with the elite member
         let mut new_population = vec![];
-        let mut new_innovations: HashMap<(usize, usize), usize> = HashMap::new(); //ensure duplicate innovations get the same innov number
         // NOTE: we don't actually need to track existing innovations, just have to number newly created ones
         for (spec, slots) in species.iter_mut().zip(reproductive_slots) {
             assert_ne!(spec.members.len(), 0, "All species have at least 1 member before repopulating.");
             spec.species_fitness = None; //reset this just because
+            spec.age += 1;
             spec.members.truncate((spec.members.len() / 2).max(1)); //remove half but keep at least 1 for populating
-            spec.choose_type_specimen(); //TODO need to ensure every species has members
+            config.speciation.choose_representative(spec, &config); //TODO need to ensure every species has members
 
             // partially fill new_population with all children of this species, depending on allotted slots
-            spec.populate(&mut new_population, slots, &mut self.innovator, &mut new_innovations);
+            spec.populate(&mut new_population, slots, &config, &mut self.innovator, innovations);
 
             // finally remove all members from this species and insert into new_population
             new_population.append(&mut spec.members);
         }
 
         assert_eq!(self.population_size, new_population.len(), "New population size matches specified population size.");
+        for phenotype in new_population.iter_mut().filter(|p| p.id.is_some()) {
+            phenotype.age += 1; //elites survive, children get their ids once sorted
+        }
 
         // assign all phenotypes to new species
-        Species::sort_species(&mut species, new_population, &mut self.species_counter);
+        config.speciation.speciate(&mut species, new_population, &mut self.species_counter, &config);
         self.species = species; //ensure we replace the population
+        self.assign_ids(self.generation_number + 1); //children belong to the generation about to start
+
+        let current_ids: BTreeSet<usize> = self.species.iter().map(|s| s.id).collect();
+        let change = SpeciesChange {
+            generation: self.generation_number,
+            created: current_ids.difference(&previous_ids).cloned().collect(),
+            extinct: previous_ids.difference(&current_ids).cloned().collect(),
+        };
+        if self.config.track_species_history {
+            // a new species split off from wherever its founder, or the founder's fitter parent, was last generation
+            for spec in self.species.iter().filter(|s| change.created.contains(&s.id)) {
+                let parent = spec.members.first()
+                    .and_then(|founder| founder.id.into_iter().chain(founder.parents.first().copied()).find_map(|i| previous_species.get(&i).copied()));
+                self.species_history.record_split(spec.id, parent, self.generation_number + 1);
+            }
+        }
+        for reporter in &mut self.reporters {
+            reporter.species_changed(&change, &self.species);
+        }
 
         // remember to update cache and increment generation
         self.update_cache();
@@ -0,0 +1,177 @@
+use std::{collections::HashMap, fmt::Write};
+use crate::{genome::{Aggregation, ConnectionGene, NodeKind}, phenotype::Phenotype};
+
+
+
+const LAYER_SPACING: f64 = 160.0; //horizontal distance between layers in the svg
+const NODE_SPACING: f64 = 60.0; //vertical distance between nodes in the same layer
+const NODE_RADIUS: f64 = 16.0;
+const MARGIN: f64 = 40.0;
+const MAX_PEN_WIDTH: f64 = 6.0;
+
+
+/// Fill colour for each kind of node
+fn node_color(kind: NodeKind) -> &'static str {
+    match kind {
+        NodeKind::Bias => "#f4d35e",
+        NodeKind::Input => "#8ecae6",
+        NodeKind::Output => "#f28482",
+        NodeKind::Hidden => "#e0e0e0",
+    }
+}
+
+
+/// Edge colour shows the sign of the weight
+fn edge_color(conn: &ConnectionGene) -> &'static str {
+    if !conn.enabled {
+        "#9e9e9e"
+    } else if conn.weight >= 0.0 {
+        "#2a9d8f"
+    } else {
+        "#e63946"
+    }
+}
+
+
+/// Edge thickness shows the magnitude of the weight, capped so huge weights stay readable
+fn edge_width(conn: &ConnectionGene) -> f64 {
+    (0.5 + conn.weight.abs()).min(MAX_PEN_WIDTH)
+}
+
+
+impl Phenotype {
+    /// Write the network as a Graphviz DOT graph
+    /// Disabled connections are only drawn (dashed) if show_disabled is set
+    pub fn to_dot(&self, show_disabled: bool) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph phenotype {{").unwrap(); //writing to a String can't fail
+        writeln!(dot, "    rankdir=LR;").unwrap();
+        writeln!(dot, "    node [style=filled, fontname=\"Helvetica\"];").unwrap();
+
+        // keep inputs and outputs lined up on opposite sides
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        for node in &self.genome.node_genes {
+            let kind = self.genome.node_kind(node.id);
+            let (shape, label) = match kind {
+                NodeKind::Bias => ("box", format!("bias {}", node.id)),
+                NodeKind::Input => ("box", format!("in {}", node.id)),
+                NodeKind::Output => ("doublecircle", format!("out {}", node.id)),
+                NodeKind::Hidden => ("circle", format!("{}", node.id)),
+            };
+            let label = match node.aggregation {
+                Aggregation::Sum => label,
+                other => format!("{} ({:?})", label, other).to_lowercase(),
+            };
+            match kind {
+                NodeKind::Bias | NodeKind::Input => inputs.push(node.id),
+                NodeKind::Output => outputs.push(node.id),
+                NodeKind::Hidden => {},
+            }
+            writeln!(dot, "    n{} [label=\"{}\", shape={}, fillcolor=\"{}\"];", node.id, label, shape, node_color(kind)).unwrap();
+        }
+        for (rank, ids) in [("source", &inputs), ("sink", &outputs)] {
+            let names = ids.iter().map(|id| format!("n{}", id)).collect::<Vec<_>>().join("; ");
+            writeln!(dot, "    {{ rank={}; {}; }}", rank, names).unwrap();
+        }
+
+        for conn in &self.genome.connection_genes {
+            if !conn.enabled && !show_disabled {
+                continue;
+            }
+            let style = if conn.enabled { "solid" } else { "dashed" };
+            writeln!(
+                dot,
+                "    n{} -> n{} [label=\"{:.3}\", color=\"{}\", penwidth={:.2}, style={}];",
+                conn.in_node, conn.out_node, conn.weight, edge_color(conn), edge_width(conn), style,
+            ).unwrap();
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+
+    /// Render the network as a self-contained SVG image, no Graphviz required
+    /// Nodes are laid out in layers by their depth in the topological order
+    pub fn to_svg(&self, show_disabled: bool) -> String {
+        // depth of each node is the longest path from any source
+        let mut depth: HashMap<usize, usize> = HashMap::new();
+        let incoming = self.genome.connection_genes.iter()
+            .filter(|conn| conn.enabled)
+            .fold(HashMap::<usize, Vec<usize>>::new(), |mut acc, conn| {
+                acc.entry(conn.out_node).or_default().push(conn.in_node);
+                acc
+            });
+        for &node_id in &self.toposorted_nodes {
+            let d = match self.genome.node_kind(node_id) {
+                NodeKind::Bias | NodeKind::Input => 0,
+                _ => incoming.get(&node_id)
+                    .map(|ins| ins.iter().map(|i| depth.get(i).map_or(1, |d| d + 1)).max().unwrap_or(1))
+                    .unwrap_or(1),
+            };
+            depth.insert(node_id, d);
+        }
+        for node in &self.genome.node_genes {
+            depth.entry(node.id).or_insert(1); //nodes left out when compiling only output ancestors
+        }
+
+        // outputs always sit in the final layer
+        let output_layer = self.genome.node_genes.iter()
+            .filter(|n| self.genome.node_kind(n.id) == NodeKind::Hidden)
+            .map(|n| depth[&n.id] + 1)
+            .max()
+            .unwrap_or(1);
+        for node in &self.genome.node_genes {
+            if self.genome.node_kind(node.id) == NodeKind::Output {
+                depth.insert(node.id, output_layer);
+            }
+        }
+
+        // bucket nodes into their layers, keeping ids in order for a stable picture
+        let mut layers: Vec<Vec<usize>> = vec![vec![]; output_layer + 1];
+        for node in &self.genome.node_genes {
+            layers[depth[&node.id]].push(node.id);
+        }
+        let tallest = layers.iter().map(|l| l.len()).max().unwrap_or(1).max(1);
+        let width = 2.0 * MARGIN + LAYER_SPACING * output_layer as f64;
+        let height = 2.0 * MARGIN + NODE_SPACING * (tallest - 1) as f64;
+
+        let mut positions: HashMap<usize, (f64, f64)> = HashMap::new();
+        for (l, layer) in layers.iter().enumerate() {
+            let offset = (height - NODE_SPACING * (layer.len().max(1) - 1) as f64) / 2.0; //center each layer vertically
+            for (i, &id) in layer.iter().enumerate() {
+                positions.insert(id, (MARGIN + LAYER_SPACING * l as f64, offset + NODE_SPACING * i as f64));
+            }
+        }
+
+        let mut svg = String::new();
+        writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.0} {:.0}\">", width, height, width, height).unwrap();
+        writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>").unwrap();
+
+        // edges first so the nodes are drawn on top
+        for conn in &self.genome.connection_genes {
+            if !conn.enabled && !show_disabled {
+                continue;
+            }
+            let (x1, y1) = positions[&conn.in_node];
+            let (x2, y2) = positions[&conn.out_node];
+            let dash = if conn.enabled { "" } else { " stroke-dasharray=\"6,4\"" };
+            writeln!(
+                svg,
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{:.2}\"{}><title>{} -> {}: {:.3}</title></line>",
+                x1, y1, x2, y2, edge_color(conn), edge_width(conn), dash, conn.in_node, conn.out_node, conn.weight,
+            ).unwrap();
+        }
+
+        for node in &self.genome.node_genes {
+            let (x, y) = positions[&node.id];
+            let kind = self.genome.node_kind(node.id);
+            writeln!(svg, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"{}\" stroke=\"#333333\"/>", x, y, NODE_RADIUS, node_color(kind)).unwrap();
+            writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"Helvetica\" font-size=\"11\" text-anchor=\"middle\">{}</text>", x, y + 4.0, node.id).unwrap();
+        }
+
+        writeln!(svg, "</svg>").unwrap();
+        svg
+    }
+}
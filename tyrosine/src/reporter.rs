@@ -0,0 +1,281 @@
+use std::{cell::RefCell, fs::File, io::{self, BufWriter, Write}, path::Path, rc::Rc};
+use serde::{Deserialize, Serialize};
+use crate::{config::MutationRates, phenotype::Phenotype, species::Species};
+
+
+
+/// Summary of one evaluated generation, handed to every Reporter
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GenerationStats {
+    pub generation: usize,
+    pub best_fitness: f64,
+    pub mean_fitness: f64,
+    pub fitness_std_dev: f64,
+    pub species_count: usize,
+    pub species_sizes: Vec<usize>,
+    #[serde(default)]
+    pub species_fitnesses: Vec<f64>, //fitness used to allot slots, the sum of adjusted fitness with fitness sharing
+    #[serde(default)]
+    pub species_ages: Vec<usize>,
+    pub mean_nodes: f64,
+    pub mean_connections: f64, //enabled connections only
+    pub innovation_number: usize,
+    #[serde(default)]
+    pub mean_mutation_rates: Option<MutationRates>, //average rates the genomes' factors give this generation, with self-adaptive mutation
+    #[serde(default)]
+    pub best_penalized_fitness: Option<f64>, //fitness minus the parsimony penalty, only set with parsimony pressure
+    #[serde(default)]
+    pub mean_penalized_fitness: Option<f64>,
+}
+impl GenerationStats {
+    /// Gather statistics from the sorted species and their matching fitnesses
+    pub fn collect(generation: usize, species: &[Species], fitnesses: &[f64], innovation_number: usize) -> Self {
+        let count = fitnesses.len().max(1) as f64;
+        let mean_fitness = fitnesses.iter().sum::<f64>() / count;
+        let variance = fitnesses.iter().map(|f| (f - mean_fitness).powi(2)).sum::<f64>() / count;
+        let members = species.iter().flat_map(|s| &s.members);
+
+        let (nodes, connections) = members.fold((0, 0), |(nodes, connections), phen| (
+            nodes + phen.genome.node_genes.len(),
+            connections + phen.genome.connection_genes.iter().filter(|c| c.enabled).count(),
+        ));
+
+        GenerationStats {
+            generation,
+            best_fitness: fitnesses.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            mean_fitness,
+            fitness_std_dev: variance.sqrt(),
+            species_count: species.len(),
+            species_sizes: species.iter().map(|s| s.members.len()).collect(),
+            species_fitnesses: species.iter().map(|s| s.species_fitness.unwrap_or(0.0)).collect(),
+            species_ages: species.iter().map(|s| s.age).collect(),
+            mean_nodes: nodes as f64 / count,
+            mean_connections: connections as f64 / count,
+            innovation_number,
+            mean_mutation_rates: None,
+            best_penalized_fitness: None,
+            mean_penalized_fitness: None,
+        }
+    }
+}
+
+
+
+/// Species created and lost during one round of speciation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpeciesChange {
+    pub generation: usize,
+    pub created: Vec<usize>, //species ids
+    pub extinct: Vec<usize>, //species ids
+}
+
+
+
+/// Hooks called by Population while evolving, every hook is optional
+pub trait Reporter {
+    /// Called when a generation starts being evolved
+    fn generation_start(&mut self, _generation: usize) {}
+
+    /// Called once fitness has been assigned and the population sorted
+    fn post_evaluate(&mut self, _stats: &GenerationStats) {}
+
+    /// Called after the next generation has been sorted into species
+    fn species_changed(&mut self, _change: &SpeciesChange, _species: &[Species]) {}
+
+    /// Called when an individual beats the best fitness seen so far
+    fn new_champion(&mut self, _generation: usize, _champion: &Phenotype, _fitness: f64) {}
+
+    /// Called when the user finishes the run
+    fn run_end(&mut self, _generation: usize) {}
+}
+
+
+
+/// Lets the user keep a handle to a reporter after handing it to a Population
+impl<R: Reporter> Reporter for Rc<RefCell<R>> {
+    fn generation_start(&mut self, generation: usize) {
+        self.borrow_mut().generation_start(generation);
+    }
+
+    fn post_evaluate(&mut self, stats: &GenerationStats) {
+        self.borrow_mut().post_evaluate(stats);
+    }
+
+    fn species_changed(&mut self, change: &SpeciesChange, species: &[Species]) {
+        self.borrow_mut().species_changed(change, species);
+    }
+
+    fn new_champion(&mut self, generation: usize, champion: &Phenotype, fitness: f64) {
+        self.borrow_mut().new_champion(generation, champion, fitness);
+    }
+
+    fn run_end(&mut self, generation: usize) {
+        self.borrow_mut().run_end(generation);
+    }
+}
+
+
+
+/// Prints a short summary of every generation
+#[derive(Debug, Default)]
+pub struct StdoutReporter {
+    pub show_species: bool, //print species sizes every generation
+}
+impl StdoutReporter {
+    pub fn new(show_species: bool) -> Self {
+        StdoutReporter { show_species }
+    }
+}
+impl Reporter for StdoutReporter {
+    fn post_evaluate(&mut self, stats: &GenerationStats) {
+        println!(
+            "generation {}: best {:.4}, mean {:.4} (sd {:.4}), {} species, {:.1} nodes, {:.1} connections, innov {}",
+            stats.generation, stats.best_fitness, stats.mean_fitness, stats.fitness_std_dev, stats.species_count,
+            stats.mean_nodes, stats.mean_connections, stats.innovation_number,
+        );
+        if let (Some(best), Some(mean)) = (stats.best_penalized_fitness, stats.mean_penalized_fitness) {
+            println!("    penalized: best {:.4}, mean {:.4}", best, mean);
+        }
+        if self.show_species {
+            println!("    species sizes: {:?}", stats.species_sizes);
+        }
+    }
+
+    fn species_changed(&mut self, change: &SpeciesChange, _species: &[Species]) {
+        if !change.created.is_empty() || !change.extinct.is_empty() {
+            println!("    species created: {:?}, extinct: {:?}", change.created, change.extinct);
+        }
+    }
+
+    fn new_champion(&mut self, generation: usize, _champion: &Phenotype, fitness: f64) {
+        println!("    new champion in generation {} with fitness {:.4}", generation, fitness);
+    }
+
+    fn run_end(&mut self, generation: usize) {
+        println!("run ended after {} generations", generation);
+    }
+}
+
+
+
+/// Writes one CSV row per generation, per-species values are separated by semicolons
+pub struct CsvReporter<W: Write> {
+    writer: W,
+    header_written: bool,
+    error: Option<io::Error>, //first error encountered, reporting never interrupts evolution
+}
+impl CsvReporter<BufWriter<File>> {
+    /// Create a reporter writing to a new file at the given path
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(CsvReporter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+impl<W: Write> CsvReporter<W> {
+    pub fn new(writer: W) -> Self {
+        CsvReporter { writer, header_written: false, error: None }
+    }
+
+    /// The first write error encountered, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Borrow the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    fn write_row(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "generation,best_fitness,mean_fitness,fitness_std_dev,species_count,species_sizes,species_fitnesses,species_ages,mean_nodes,mean_connections,innovation_number,mean_add_connection,mean_add_node,mean_remove_connection,mean_remove_node,mean_weight_rate,mean_toggle,mean_perturbation_std,best_penalized_fitness,mean_penalized_fitness")?;
+            self.header_written = true;
+        }
+        let sizes = stats.species_sizes.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(";");
+        let species_fitnesses = stats.species_fitnesses.iter().map(|f| f.to_string()).collect::<Vec<_>>().join(";");
+        let ages = stats.species_ages.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(";");
+        let rates = match &stats.mean_mutation_rates {
+            Some(r) => [r.add_connection, r.add_node, r.remove_connection, r.remove_node, r.weight, r.toggle, r.perturbation_std]
+                .map(|r| r.to_string())
+                .join(","),
+            None => ",,,,,,".to_string(), //empty columns without self-adaptation
+        };
+        let optional = |value: Option<f64>| value.map_or(String::new(), |v| v.to_string());
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            stats.generation, stats.best_fitness, stats.mean_fitness, stats.fitness_std_dev, stats.species_count,
+            sizes, species_fitnesses, ages, stats.mean_nodes, stats.mean_connections, stats.innovation_number, rates,
+            optional(stats.best_penalized_fitness), optional(stats.mean_penalized_fitness),
+        )
+    }
+}
+impl<W: Write> Reporter for CsvReporter<W> {
+    fn post_evaluate(&mut self, stats: &GenerationStats) {
+        if let Err(e) = self.write_row(stats) {
+            self.error.get_or_insert(e);
+        }
+    }
+
+    fn run_end(&mut self, _generation: usize) {
+        if let Err(e) = self.writer.flush() {
+            self.error.get_or_insert(e);
+        }
+    }
+}
+
+
+
+/// Writes every generation's statistics as one JSON object per line
+pub struct JsonLinesReporter<W: Write> {
+    writer: W,
+    error: Option<io::Error>, //first error encountered, reporting never interrupts evolution
+}
+impl JsonLinesReporter<BufWriter<File>> {
+    /// Create a reporter writing to a new file at the given path
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(JsonLinesReporter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+impl<W: Write> JsonLinesReporter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesReporter { writer, error: None }
+    }
+
+    /// The first write error encountered, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Borrow the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    fn write_line(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, stats)?;
+        writeln!(self.writer)
+    }
+}
+impl<W: Write> Reporter for JsonLinesReporter<W> {
+    fn post_evaluate(&mut self, stats: &GenerationStats) {
+        if let Err(e) = self.write_line(stats) {
+            self.error.get_or_insert(e);
+        }
+    }
+
+    fn run_end(&mut self, _generation: usize) {
+        if let Err(e) = self.writer.flush() {
+            self.error.get_or_insert(e);
+        }
+    }
+}
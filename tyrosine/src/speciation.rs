@@ -0,0 +1,370 @@
+use std::{any::Any, fmt, ops::Deref, sync::Arc};
+use rand::seq::SliceRandom;
+use serde::{de::Deserializer, ser::{Error, Serializer}, Deserialize, Serialize};
+use crate::{config::Config, genome::Genome, phenotype::Phenotype, species::{Species, SpeciesCounter, SPECIES_THRESHOLD}};
+
+
+
+/// A way of dividing the population into species, set on the config
+pub trait Speciation: Any + Send + Sync {
+    /// Name used when debugging or comparing configs
+    fn name(&self) -> &str;
+
+    /// Sort phenotypes into the species, creating and removing species as needed
+    /// Every species is empty when this is called, species left without members should be removed
+    fn speciate(&self, species: &mut Vec<Species>, phenotypes: Vec<Phenotype>, species_counter: &mut SpeciesCounter, config: &Config);
+
+    /// Place a single newcomer into the species as they are, used by steady-state evolution
+    /// Defaults to first-fit against the current type specimens
+    fn speciate_one(&self, species: &mut Vec<Species>, phenotype: Phenotype, species_counter: &mut SpeciesCounter, config: &Config) {
+        fit_one(species, phenotype, species_counter, SPECIES_THRESHOLD, config.small_genome_size);
+    }
+
+    /// Pick the type specimen the next generation is compared against, called before a surviving species reproduces
+    fn choose_representative(&self, species: &mut Species, _config: &Config) {
+        species.choose_type_specimen();
+    }
+}
+
+
+
+/// Sort every phenotype into the first compatible species in a random order, as in the NEAT paper
+/// Not cached on purpose, a phenotype stops at its first match and never meets the same representative twice
+pub(crate) fn first_fit(species: &mut Vec<Species>, mut phenotypes: Vec<Phenotype>, species_counter: &mut SpeciesCounter, threshold: f64, small_genome_size: usize) {
+
+    // first ensure all species are devoid of members
+    for s in species.iter() {
+        assert_eq!(s.members.len(), 0, "All species have 0 members before sorting population.");
+    }
+
+    let mut rng = rand::rng();
+    phenotypes.shuffle(&mut rng); //delete biases here
+
+    // if no species, then just create one from the first phenotype
+    if species.is_empty() {
+        let new_species = Species::new(&phenotypes[0].genome, species_counter.next());
+        species.push(new_species);
+    }
+
+    for phenotype in phenotypes {
+        fit_one(species, phenotype, species_counter, threshold, small_genome_size);
+    }
+
+    // finally remove any species with 0 allocated members
+    species.retain(|s| !s.members.is_empty());
+}
+
+
+
+/// Put one phenotype into the first compatible species in a random order, or a new species if none match
+fn fit_one(species: &mut Vec<Species>, phenotype: Phenotype, species_counter: &mut SpeciesCounter, threshold: f64, small_genome_size: usize) {
+    let mut indices: Vec<usize> = (0..species.len()).collect();
+    indices.shuffle(&mut rand::rng()); //shuffle to reduce biases (in a way that doesn't cause borrow errors)
+
+    for i in indices {
+        let cur_species = &mut species[i];
+        if Species::compatibility_distance_with(&phenotype.genome, &cur_species.type_specimen, small_genome_size) < threshold {
+            cur_species.members.push(phenotype); //push to species
+            return;
+        }
+    }
+
+    // didn't match any existing species, create new species
+    let mut new_species = Species::new(&phenotype.genome, species_counter.next());
+    new_species.members.push(phenotype); //push this phenotype
+    species.push(new_species);
+}
+
+
+
+/// The original NEAT speciation, compares against a random member of the previous generation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct FirstFit {
+    pub threshold: f64, //compatibility distance below which a phenotype joins a species
+}
+impl Default for FirstFit {
+    fn default() -> Self {
+        FirstFit { threshold: SPECIES_THRESHOLD }
+    }
+}
+impl Speciation for FirstFit {
+    fn name(&self) -> &str {
+        "first_fit"
+    }
+
+    fn speciate(&self, species: &mut Vec<Species>, phenotypes: Vec<Phenotype>, species_counter: &mut SpeciesCounter, config: &Config) {
+        first_fit(species, phenotypes, species_counter, self.threshold, config.small_genome_size);
+    }
+
+    fn speciate_one(&self, species: &mut Vec<Species>, phenotype: Phenotype, species_counter: &mut SpeciesCounter, config: &Config) {
+        fit_one(species, phenotype, species_counter, self.threshold, config.small_genome_size);
+    }
+}
+
+
+
+/// First-fit speciation against the centroid of each species' previous generation
+/// Centroids are less noisy than a random member, so species drift less from one generation to the next
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct CentroidFit {
+    pub threshold: f64, //compatibility distance below which a phenotype joins a species
+}
+impl Default for CentroidFit {
+    fn default() -> Self {
+        CentroidFit { threshold: SPECIES_THRESHOLD }
+    }
+}
+impl Speciation for CentroidFit {
+    fn name(&self) -> &str {
+        "centroid_fit"
+    }
+
+    fn speciate(&self, species: &mut Vec<Species>, phenotypes: Vec<Phenotype>, species_counter: &mut SpeciesCounter, config: &Config) {
+        first_fit(species, phenotypes, species_counter, self.threshold, config.small_genome_size);
+    }
+
+    fn speciate_one(&self, species: &mut Vec<Species>, phenotype: Phenotype, species_counter: &mut SpeciesCounter, config: &Config) {
+        fit_one(species, phenotype, species_counter, self.threshold, config.small_genome_size);
+    }
+
+    fn choose_representative(&self, species: &mut Species, _config: &Config) {
+        species.type_specimen = species.centroid();
+    }
+}
+
+
+
+/// Clusters the population into a fixed number of species with k-medoids
+/// Species are seeded from the previous medoids so they keep their ids, new ones start from the most distant phenotypes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct KMedoids {
+    pub species_count: usize,
+    pub max_iterations: usize, //reassignment rounds before giving up on convergence
+    pub cache_distances: bool, //remember distances to medoids that didn't move between rounds
+}
+impl Default for KMedoids {
+    fn default() -> Self {
+        KMedoids { species_count: 5, max_iterations: 10, cache_distances: true }
+    }
+}
+impl KMedoids {
+    /// Index of the genome with the smallest total distance to the others
+    fn medoid(genomes: &[&Genome], small_genome_size: usize) -> usize {
+        (0..genomes.len())
+            .map(|i| (i, genomes.iter().map(|g| Species::compatibility_distance_with(genomes[i], g, small_genome_size)).sum::<f64>()))
+            .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap_or(std::cmp::Ordering::Less))
+            .map_or(0, |(i, _)| i)
+    }
+}
+impl Speciation for KMedoids {
+    fn name(&self) -> &str {
+        "k_medoids"
+    }
+
+    fn speciate(&self, species: &mut Vec<Species>, phenotypes: Vec<Phenotype>, species_counter: &mut SpeciesCounter, config: &Config) {
+        for s in species.iter() {
+            assert_eq!(s.members.len(), 0, "All species have 0 members before sorting population.");
+        }
+        let k = self.species_count.clamp(1, phenotypes.len().max(1));
+        species.truncate(k);
+        let mut cache = DistanceCache::new(self.cache_distances, phenotypes.len());
+        let mut medoids: Vec<Option<usize>> = vec![None; species.len()]; //index of each medoid in phenotypes, None if from the last generation
+
+        // seed the missing species with the phenotypes furthest from every existing one
+        while species.len() < k {
+            let furthest = phenotypes.iter()
+                .enumerate()
+                .map(|(p, phenotype)| species.iter()
+                    .enumerate()
+                    .map(|(s, spec)| cache.distance(s, p, || Species::compatibility_distance_with(&phenotype.genome, &spec.type_specimen, config.small_genome_size)))
+                    .fold(f64::INFINITY, f64::min))
+                .enumerate()
+                .max_by(|x, y| x.1.partial_cmp(&y.1).unwrap_or(std::cmp::Ordering::Less))
+                .map(|(i, _)| i);
+            match furthest {
+                Some(i) => {
+                    species.push(Species::new(&phenotypes[i].genome, species_counter.next()));
+                    medoids.push(Some(i));
+                },
+                None => break, //no phenotypes at all
+            }
+        }
+
+        // alternate between assigning to the nearest medoid and moving each medoid to the middle of its cluster
+        let mut assignment = vec![usize::MAX; phenotypes.len()];
+        for _ in 0..self.max_iterations.max(1) {
+            let mut changed = false;
+            for (p, phenotype) in phenotypes.iter().enumerate() {
+                let nearest = species.iter()
+                    .enumerate()
+                    .map(|(s, spec)| cache.distance(s, p, || Species::compatibility_distance_with(&phenotype.genome, &spec.type_specimen, config.small_genome_size)))
+                    .enumerate()
+                    .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap_or(std::cmp::Ordering::Less))
+                    .map_or(0, |(i, _)| i);
+                if assignment[p] != nearest {
+                    assignment[p] = nearest;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+            for (i, s) in species.iter_mut().enumerate() {
+                let (indices, cluster): (Vec<usize>, Vec<&Genome>) = phenotypes.iter()
+                    .zip(&assignment)
+                    .enumerate()
+                    .filter(|(_, (_, a))| **a == i)
+                    .map(|(p, (phenotype, _))| (p, &phenotype.genome))
+                    .unzip();
+                if cluster.is_empty() {
+                    continue;
+                }
+                let medoid = KMedoids::medoid(&cluster, config.small_genome_size);
+                if medoids[i] != Some(indices[medoid]) {
+                    medoids[i] = Some(indices[medoid]);
+                    s.type_specimen = cluster[medoid].clone();
+                    cache.forget(i);
+                }
+            }
+        }
+
+        for (phenotype, i) in phenotypes.into_iter().zip(assignment) {
+            species[i].members.push(phenotype);
+        }
+        species.retain(|s| !s.members.is_empty());
+    }
+
+    fn speciate_one(&self, species: &mut Vec<Species>, phenotype: Phenotype, species_counter: &mut SpeciesCounter, config: &Config) {
+        let nearest = species.iter()
+            .map(|s| Species::compatibility_distance_with(&phenotype.genome, &s.type_specimen, config.small_genome_size))
+            .enumerate()
+            .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap_or(std::cmp::Ordering::Less))
+            .map(|(i, _)| i);
+        match nearest {
+            Some(i) => species[i].members.push(phenotype),
+            None => { //only when every species died out
+                let mut new_species = Species::new(&phenotype.genome, species_counter.next());
+                new_species.members.push(phenotype);
+                species.push(new_species);
+            },
+        }
+    }
+
+    fn choose_representative(&self, species: &mut Species, config: &Config) {
+        let genomes = species.members.iter().map(|p| &p.genome).collect::<Vec<_>>();
+        species.type_specimen = genomes[KMedoids::medoid(&genomes, config.small_genome_size)].clone();
+    }
+}
+
+
+
+/// Memoised distances from each phenotype to each species representative during one round of speciation
+/// Used by k-medoids, whose reassignment rounds compare the same pairs again
+/// First-fit and centroid-fit are out of scope, they compare each pair at most once so a cache would never hit
+/// Species and phenotypes are addressed by index, forget a species whenever its representative changes
+pub struct DistanceCache {
+    enabled: bool,
+    phenotypes: usize,
+    distances: Vec<Vec<Option<f64>>>, //indexed by species then phenotype
+}
+impl DistanceCache {
+    /// A disabled cache computes every distance again
+    pub fn new(enabled: bool, phenotypes: usize) -> Self {
+        DistanceCache { enabled, phenotypes, distances: Vec::new() }
+    }
+
+
+    /// Distance between a phenotype and a species representative, computed only if it isn't known yet
+    pub fn distance<F: FnOnce() -> f64>(&mut self, species: usize, phenotype: usize, compute: F) -> f64 {
+        if !self.enabled {
+            return compute();
+        }
+        if self.distances.len() <= species {
+            self.distances.resize(species + 1, vec![]);
+        }
+        let row = &mut self.distances[species];
+        if row.is_empty() {
+            row.resize(self.phenotypes, None);
+        }
+        *row[phenotype].get_or_insert_with(compute)
+    }
+
+
+    /// Drop every distance to a species, for when its representative changes
+    pub fn forget(&mut self, species: usize) {
+        if let Some(row) = self.distances.get_mut(species) {
+            row.clear();
+        }
+    }
+}
+
+
+
+/// The speciation strategy used by a config, first-fit by default
+/// Built-in strategies are saved with their parameters, saving a custom strategy fails
+#[derive(Clone)]
+pub struct SpeciationStrategy(Arc<dyn Speciation>);
+impl Default for SpeciationStrategy {
+    fn default() -> Self {
+        SpeciationStrategy::new(FirstFit::default())
+    }
+}
+impl SpeciationStrategy {
+    pub fn new<S: Speciation + 'static>(strategy: S) -> Self {
+        SpeciationStrategy(Arc::new(strategy))
+    }
+}
+impl Deref for SpeciationStrategy {
+    type Target = dyn Speciation;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl fmt::Debug for SpeciationStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.name())
+    }
+}
+impl PartialEq for SpeciationStrategy {
+    /// Strategies are compared by name
+    fn eq(&self, other: &Self) -> bool {
+        self.0.name() == other.0.name()
+    }
+}
+impl Serialize for SpeciationStrategy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let strategy: &dyn Any = self.0.as_ref();
+        let saved = if let Some(first_fit) = strategy.downcast_ref::<FirstFit>() {
+            SavedSpeciation::FirstFit(*first_fit)
+        } else if let Some(centroid_fit) = strategy.downcast_ref::<CentroidFit>() {
+            SavedSpeciation::CentroidFit(*centroid_fit)
+        } else if let Some(k_medoids) = strategy.downcast_ref::<KMedoids>() {
+            SavedSpeciation::KMedoids(*k_medoids)
+        } else {
+            return Err(S::Error::custom(format!("custom speciation strategy `{}` can't be saved", self.0.name())));
+        };
+        saved.serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for SpeciationStrategy {
+    /// Unknown strategy names are an error rather than falling back to first-fit
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match SavedSpeciation::deserialize(deserializer)? {
+            SavedSpeciation::FirstFit(first_fit) => SpeciationStrategy::new(first_fit),
+            SavedSpeciation::CentroidFit(centroid_fit) => SpeciationStrategy::new(centroid_fit),
+            SavedSpeciation::KMedoids(k_medoids) => SpeciationStrategy::new(k_medoids),
+        })
+    }
+}
+
+
+
+/// The built-in strategies as they're saved, tagged with the same names they report
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+enum SavedSpeciation {
+    FirstFit(FirstFit),
+    CentroidFit(CentroidFit),
+    KMedoids(KMedoids),
+}
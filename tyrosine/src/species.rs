@@ -1,18 +1,26 @@
-use std::{collections::{BTreeSet, HashMap}, mem};
-use rand::seq::{index::sample, IndexedRandom, SliceRandom};
-use crate::{genome::{Genome, GlobalInnovator}, phenotype::Phenotype};
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, mem};
+use rand::{distr::{weighted::WeightedIndex, Distribution}, seq::{index::sample, IndexedRandom}};
+use serde::{Deserialize, Serialize};
+use crate::{config::Config, genome::{Aggregation, ConnectionGene, Genome, GlobalInnovator, NodeGene, NodeKind}, phenotype::Phenotype, speciation::first_fit};
 
 
 
+#[derive(Serialize, Deserialize, Debug)]
 pub struct SpeciesCounter {
     pub id: usize,
 }
+impl Default for SpeciesCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl SpeciesCounter {
     pub fn new() -> Self {
         SpeciesCounter { id: 0 }
     }
 
     /// Get the next species id and increment internally
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> usize {
         let id = self.id;
         self.id += 1;
@@ -21,17 +29,22 @@ impl SpeciesCounter {
 }
 
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Species {
     pub type_specimen: Genome, //may be part of the active population, or not
     pub members: Vec<Phenotype>,
     pub id: usize, //for non-crucial historical reasons
     pub species_fitness: Option<f64>,
+    pub best_fitness: Option<f64>, //best raw fitness among the members
+    #[serde(default)]
+    pub age: usize, //generations this species has survived
 }
 const C1: f64 = 1.0; //excess weight
 const C2: f64 = 1.0; //disjoint weight
 const C3: f64 = 0.4; //weight difference multiplier
-const SPECIES_THRESHOLD: f64 = 3.0; //used to determine if two genomes are the same species
+const C4: f64 = 0.4; //node difference multiplier
+pub(crate) const SPECIES_THRESHOLD: f64 = 3.0; //used to determine if two genomes are the same species
+pub(crate) const SMALL_GENOME_SIZE: usize = 20; //genomes smaller than this aren't normalized, as in the NEAT paper
 impl Species {
     /// Create a new species from a genome (sets it as the type specimen)
     pub fn new(genome: &Genome, id: usize) -> Self {
@@ -40,47 +53,33 @@ impl Species {
             members: Vec::new(),
             id,
             species_fitness: None,
+            best_fitness: None,
+            age: 0,
         }
     }
 
 
-    /// Take phenotypes and sort them into the right species
-    pub fn sort_species(species: &mut Vec<Species>, mut phenotypes: Vec<Phenotype>, species_counter: &mut SpeciesCounter) {
-
-        // first ensure all species are devoid of members
-        for s in species.iter() {
-            assert_eq!(s.members.len(), 0, "All species have 0 members before sorting population.");
-        }
+    /// Take phenotypes and sort them into the right species with first-fit speciation
+    pub fn sort_species(species: &mut Vec<Species>, phenotypes: Vec<Phenotype>, species_counter: &mut SpeciesCounter) {
+        first_fit(species, phenotypes, species_counter, SPECIES_THRESHOLD, SMALL_GENOME_SIZE);
+    }
 
-        let mut rng = rand::rng();
-        phenotypes.shuffle(&mut rng); //delete biases here
 
-        // if no species, then just create one from the first phenotype
-        if species.len() == 0 {
-            let new_species = Species::new(&phenotypes[0].genome, species_counter.next());
-            species.push(new_species);
+    /// Multiplier on species fitness, boosting young species and penalizing old ones
+    pub fn age_multiplier(&self, config: &Config) -> f64 {
+        if self.age < config.young_species_age {
+            config.young_fitness_boost
+        } else if self.age >= config.old_species_age {
+            config.old_fitness_penalty
+        } else {
+            1.0
         }
+    }
 
-        'phen_loop: for phenotype in phenotypes {
-            let mut indices: Vec<usize> = (0..species.len()).collect();
-            indices.shuffle(&mut rng); //shuffle to reduce biases (in a way that doesn't cause borrow errors)
-
-            for i in indices {
-                let cur_species = &mut species[i];
-                if Species::compatibility_distance(&phenotype.genome, &cur_species.type_specimen) < SPECIES_THRESHOLD {
-                    cur_species.members.push(phenotype); //push to species
-                    continue 'phen_loop;
-                }
-            }
-
-            // didn't match any existing species, create new species
-            let mut new_species = Species::new(&phenotype.genome, species_counter.next());
-            new_species.members.push(phenotype); //push this phenotype
-            species.push(new_species);
-        }
 
-        // finally remove any species with 0 allocated members
-        species.retain(|s| s.members.len() > 0);
+    /// Check if this species is still young enough for its minimum slots
+    pub fn is_protected(&self, config: &Config) -> bool {
+        self.age < config.protected_species_age
     }
 
 
@@ -95,8 +94,59 @@ impl Species {
     }
 
 
+    /// An average genome of the current members, only meant for measuring distances
+    /// Keeps the connections at least half the members have with their mean weight, and the nodes they use
+    pub fn centroid(&self) -> Genome {
+        assert_ne!(self.members.len(), 0, "Species has at least 1 member before taking its centroid.");
+        let first = &self.members[0].genome;
+        let half = self.members.len() as f64 / 2.0;
+
+        // (count, weight sum, gene) for every innovation number
+        let mut connections: BTreeMap<usize, (usize, f64, &ConnectionGene)> = BTreeMap::new();
+        let mut nodes: BTreeMap<usize, (usize, f64, Vec<Aggregation>)> = BTreeMap::new();
+        for genome in self.members.iter().map(|p| &p.genome) {
+            for conn in &genome.connection_genes {
+                let entry = connections.entry(conn.innov).or_insert((0, 0.0, conn));
+                entry.0 += 1;
+                entry.1 += conn.weight;
+            }
+            for node in &genome.node_genes {
+                let entry = nodes.entry(node.id).or_insert((0, 0.0, vec![]));
+                entry.0 += 1;
+                entry.1 += node.bias;
+                entry.2.push(node.aggregation);
+            }
+        }
+
+        let connection_genes = connections.into_values()
+            .filter(|(count, _, _)| *count as f64 >= half)
+            .map(|(count, sum, conn)| ConnectionGene { weight: sum / count as f64, ..*conn })
+            .collect::<Vec<_>>();
+        let used = connection_genes.iter()
+            .flat_map(|c| [c.in_node, c.out_node])
+            .collect::<BTreeSet<_>>();
+        let node_genes = nodes.into_iter()
+            .filter(|(id, _)| first.node_kind(*id) != NodeKind::Hidden || used.contains(id)) //bias, inputs and outputs are always kept
+            .map(|(id, (count, sum, aggregations))| NodeGene {
+                id,
+                bias: sum / count as f64,
+                aggregation: *aggregations.iter() //most common aggregation
+                    .max_by_key(|a| aggregations.iter().filter(|b| b == a).count())
+                    .unwrap(), //safe unwrap, every node was seen at least once
+            })
+            .collect();
+
+        Genome {
+            node_genes,
+            connection_genes,
+            rate_factors: None,
+            ..first.clone()
+        }
+    }
+
+
     /// Fill the specified number of slots with new phenotypes.
-    pub fn populate(&mut self, vec: &mut Vec<Phenotype>, mut slots: usize, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) {
+    pub fn populate(&mut self, vec: &mut Vec<Phenotype>, mut slots: usize, config: &Config, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) {
         assert_ne!(self.members.len(), 0, "Species has at least 1 member before reproducing.");
         assert_ne!(slots, 0, "There exist at least 1 slot for this species before reproducing.");
         let mut rng = rand::rng();
@@ -104,28 +154,50 @@ impl Species {
         vec.push(members[0].clone()); //push the elite member
         slots -= 1;
 
+        // with fitness sharing parents are picked in proportion to their adjusted fitness
+        let parent_weights = if config.fitness_sharing && members.len() > 1 {
+            Species::selection_weights(&members)
+        } else {
+            None
+        };
+
         // push newly born children
         if members.len() == 1 { //asexual reproduction
             while slots > 0 {
                 let this_genome = &members.first().unwrap().genome;
-                let phenotype = Phenotype::from_mutation(this_genome, innovator, innovations);
+                let mut phenotype = Phenotype::from_mutation(this_genome, config, innovator, innovations);
+                phenotype.parents = members[0].id.into_iter().collect();
                 vec.push(phenotype);
                 slots -= 1;
             }
         } else { //sexual reproduction
             while slots > 0 {
-                let indices = sample(&mut rng, members.len(), 2);
+                let (first, second) = match &parent_weights {
+                    Some(weights) => {
+                        let first = weights.sample(&mut rng);
+                        let mut second = weights.sample(&mut rng);
+                        while second == first {
+                            second = weights.sample(&mut rng);
+                        }
+                        (first, second)
+                    },
+                    None => {
+                        let indices = sample(&mut rng, members.len(), 2);
+                        (indices.index(0), indices.index(1))
+                    },
+                };
                 let fit_parent;
                 let unfit_parent;
-                if indices.index(0) > indices.index(1) { //first parent fitter
-                    fit_parent = members.get(indices.index(0)).unwrap();
-                    unfit_parent = members.get(indices.index(1)).unwrap();
+                if first < second { //first parent fitter, members are sorted by fitness
+                    fit_parent = members.get(first).unwrap();
+                    unfit_parent = members.get(second).unwrap();
                 } else { //second parent fitter
-                    fit_parent = members.get(indices.index(1)).unwrap();
-                    unfit_parent = members.get(indices.index(0)).unwrap();
+                    fit_parent = members.get(second).unwrap();
+                    unfit_parent = members.get(first).unwrap();
                 }
                 let child_genome = Genome::crossover(&fit_parent.genome, &unfit_parent.genome);
-                let child = Phenotype::from_mutation(&child_genome, innovator, innovations);
+                let mut child = Phenotype::from_mutation(&child_genome, config, innovator, innovations);
+                child.parents = [fit_parent.id, unfit_parent.id].into_iter().flatten().collect();
                 vec.push(child);
                 slots -= 1;
             }
@@ -133,22 +205,60 @@ impl Species {
     }
 
 
-    /// Calculates how genetically different two genomes are, using NEAT's formula:
-    /// δ = c1*E/N + c2*D/N + c3*W
-    /// E = excess genes, D = disjoint genes, W = avg weight diff, N = normalizer
-    pub fn compatibility_distance(g1: &Genome, g2: &Genome) -> f64 {
-        // map innovation numbers to genes for both genomes
-        let mut g1_map = HashMap::new();
-        for conn in &g1.connection_genes {
-            g1_map.insert(conn.innov, conn);
+    /// Breed a single child without touching the members, for steady-state evolution
+    /// Members needn't be sorted, the parent with the higher fitness counts as the fitter one
+    pub fn breed_one(&self, config: &Config, innovator: &mut GlobalInnovator, innovations: &mut HashMap<(usize, usize), usize>) -> Phenotype {
+        assert_ne!(self.members.len(), 0, "Species has at least 1 member before reproducing.");
+        if self.members.len() == 1 { //asexual reproduction
+            let mut child = Phenotype::from_mutation(&self.members[0].genome, config, innovator, innovations);
+            child.parents = self.members[0].id.into_iter().collect();
+            return child;
+        }
+        let indices = sample(&mut rand::rng(), self.members.len(), 2);
+        let (mut fit_parent, mut unfit_parent) = (&self.members[indices.index(0)], &self.members[indices.index(1)]);
+        if fit_parent.fitness.unwrap_or(f64::NEG_INFINITY) < unfit_parent.fitness.unwrap_or(f64::NEG_INFINITY) {
+            (fit_parent, unfit_parent) = (unfit_parent, fit_parent);
         }
-        let mut g2_map = HashMap::new();
-        for conn in &g2.connection_genes {
-            g2_map.insert(conn.innov, conn);
+        let child_genome = Genome::crossover(&fit_parent.genome, &unfit_parent.genome);
+        let mut child = Phenotype::from_mutation(&child_genome, config, innovator, innovations);
+        child.parents = [fit_parent.id, unfit_parent.id].into_iter().flatten().collect();
+        child
+    }
+
+
+    /// Roulette weights from adjusted fitness, shifted so the weakest member still has a small chance
+    /// Returns None if the weights are unusable, in which case parents are picked uniformly
+    fn selection_weights(members: &[Phenotype]) -> Option<WeightedIndex<f64>> {
+        let adjusted = members.iter()
+            .map(|p| p.adjusted_fitness)
+            .collect::<Option<Vec<f64>>>()?;
+        let min = adjusted.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = adjusted.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let floor = (max - min).abs() * 0.01 + f64::EPSILON;
+        let finite = adjusted.iter()
+            .filter(|a| a.is_finite())
+            .count();
+        if finite < 2 {
+            return None; //need at least two distinct candidates
         }
+        WeightedIndex::new(adjusted.iter().map(|a| if a.is_finite() { a - min + floor } else { 0.0 })).ok()
+    }
+
+
+    /// Calculates how genetically different two genomes are, using NEAT's formula:
+    /// δ = c1*E/N + c2*D/N + c3*W + c4*B
+    /// E = excess genes, D = disjoint genes, W = avg weight diff, N = normalizer, B = avg node diff (bias and aggregation)
+    /// Always normalizes, so E/N + D/N never exceeds 2
+    pub fn compatibility_distance(g1: &Genome, g2: &Genome) -> f64 {
+        Species::compatibility_distance_with(g1, g2, 0)
+    }
+
 
-        // all innovation numbers from both genomes combined
-        let all_innovs: BTreeSet<usize> = g1_map.keys().chain(g2_map.keys()).cloned().collect();
+    /// Compatibility distance that skips normalization when both genomes have fewer than small_genome_size connection genes
+    /// Connection genes are kept sorted by innovation number, so both genomes are walked at once without allocating
+    pub fn compatibility_distance_with(g1: &Genome, g2: &Genome, small_genome_size: usize) -> f64 {
+        let (c1, c2) = (&g1.connection_genes, &g2.connection_genes);
+        debug_assert!(c1.is_sorted_by_key(|c| c.innov) && c2.is_sorted_by_key(|c| c.innov), "Connection genes are sorted by innovation number.");
 
         // declare variables to be calculated
         let mut matching = 0;
@@ -157,39 +267,79 @@ impl Species {
         let mut excess = 0;
 
         // determine the largest innovation numbers from each genome
-        let max1 = g1_map.keys().max().cloned().unwrap_or(0);
-        let max2 = g2_map.keys().max().cloned().unwrap_or(0);
+        let max1 = c1.last().map_or(0, |c| c.innov);
+        let max2 = c2.last().map_or(0, |c| c.innov);
 
         // for each innovation number, classify it as matching, disjoint, or excess
-        for innov in &all_innovs {
-            match (g1_map.get(innov), g2_map.get(innov)) {
-                (Some(a), Some(b)) => { //both genomes have this gene, matching
+        let (mut i, mut j) = (0, 0);
+        while i < c1.len() || j < c2.len() {
+            match (c1.get(i), c2.get(j)) {
+                (Some(a), Some(b)) if a.innov == b.innov => { //both genomes have this gene, matching
                     matching += 1;
                     weight_diff += (a.weight - b.weight).abs();
-                }
-                (Some(_), None) if *innov <= max2 => {
-                    disjoint += 1;
+                    i += 1;
+                    j += 1;
                 },
-                (None, Some(_)) if *innov <= max1 => {
-                    disjoint += 1;
+                (Some(a), b) if b.is_none_or(|b| a.innov < b.innov) => { //only in the first genome
+                    if a.innov <= max2 {
+                        disjoint += 1;
+                    } else {
+                        excess += 1; //it's in one genome after the other's max innov number
+                    }
+                    i += 1;
                 },
-                _ => {
-                    excess += 1; //it's in one genome after the other's max innov number
+                (_, Some(b)) => { //only in the second genome
+                    if b.innov <= max1 {
+                        disjoint += 1;
+                    } else {
+                        excess += 1;
+                    }
+                    j += 1;
                 },
+                _ => unreachable!(), //the loop ends once both genomes are used up
             }
         }
 
-        let n = all_innovs.len().max(1); //normalizer, size of larger genome (or 1 to avoid division by 0)
+        // normalizer, size of the larger genome (or 1 for small genomes and to avoid division by 0)
+        let n = if c1.len() < small_genome_size && c2.len() < small_genome_size {
+            1
+        } else {
+            c1.len().max(c2.len()).max(1)
+        };
         let w = if matching > 0 { //average weight difference for matching genes
             weight_diff / matching as f64
         } else {
-            100.0 //fallback if no matching genes, arbitrarily large number
+            0.0 //no weights to compare, only the excess and disjoint terms count
         };
 
-        // compatibility distance formula from NEAT paper
+        // average difference of nodes both genomes have, 0 when node biases and aggregations are left alone
+        // node genes are kept sorted by id, so walk both at once, skipping the inputs which never change
+        let mut node_diff = 0.0;
+        let mut matching_nodes = 0;
+        let (mut i, mut j) = (g1.num_inputs, g2.num_inputs);
+        while i < g1.node_genes.len() && j < g2.node_genes.len() {
+            let (n1, n2) = (&g1.node_genes[i], &g2.node_genes[j]);
+            if n1.id == n2.id {
+                node_diff += (n1.bias - n2.bias).abs();
+                if n1.aggregation != n2.aggregation {
+                    node_diff += 1.0;
+                }
+                matching_nodes += 1;
+            }
+            if n1.id <= n2.id {
+                i += 1;
+            }
+            if n2.id <= n1.id {
+                j += 1;
+            }
+        }
+        let b = node_diff / matching_nodes.max(1) as f64;
+
+        // compatibility distance formula from NEAT paper, plus a node term
         C1 * (excess as f64) / n as f64 +
         C2 * (disjoint as f64) / n as f64 +
-        C3 * w
+        C3 * w +
+        C4 * b
     }
 }
 